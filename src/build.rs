@@ -1,103 +1,802 @@
 use clap::ArgEnum;
-use miette::{IntoDiagnostic, Result, WrapErr};
+use indexmap::IndexMap;
+use miette::{IntoDiagnostic, Report, Result, WrapErr};
+use serde::{Deserialize, Serialize};
+use similar::TextDiff;
 use std::{
-    collections::HashSet,
+    collections::{BTreeSet, HashMap, HashSet},
     fs::{self},
-    path::PathBuf,
+    path::{Path, PathBuf},
 };
-use tinytemplate::TinyTemplate;
 
-use crate::{comment_parser, errors::SpecError, formats, git::get_local_repo_path, toml_parser};
+use crate::{
+    comment_parser::{self, Block},
+    errors::SpecError,
+    features::Features,
+    formats,
+    git::get_local_repo_path,
+    outline, references, requirements, rust_parser, template,
+    toml_parser::{self, Config, Manifest, Metadata, SectionMode, Specification},
+    traceability::{self, Matrix},
+};
+
+pub use crate::{
+    features::Edition, formats::OutputFile, requirements::Requirement, toml_parser::Target,
+    traceability::Test,
+};
 
 /// The different specification format that cargo-spec can output
-#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum)]
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord, ArgEnum, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
 pub enum OutputFormat {
     /// Markdown (the default)
     Markdown,
 
     /// Respec
     Respec,
+
+    /// A standalone HTML page
+    Html,
+
+    /// Typst, a typesetting system
+    Typst,
+
+    /// PDF (produced with Typst)
+    Pdf,
+
+    /// An mdBook (a directory with one markdown file per chapter)
+    Mdbook,
+
+    /// JSON, with what was extracted for each section and where it comes from
+    Json,
+}
+
+impl OutputFormat {
+    /// The file (or directory, for mdbook) the specification is written to by default
+    pub fn default_output(self) -> PathBuf {
+        use OutputFormat::*;
+        PathBuf::from(match self {
+            Markdown => "specification.md",
+            Respec | Html => "specification.html",
+            Typst => "specification.typ",
+            Pdf => "specification.pdf",
+            Mdbook => "book",
+            Json => "specification.json",
+        })
+    }
+}
+
+/// A specification built in memory
+#[derive(Debug)]
+pub struct Output {
+    /// the files of the specification (there's only one, except for mdbook)
+    pub files: Vec<OutputFile>,
+    /// the files the specification was built from (the template, the files of each section, etc.)
+    pub dependencies: HashSet<PathBuf>,
+    /// problems that didn't prevent the specification from being built
+    pub warnings: Vec<Report>,
+    /// the normative requirements of the specification, along with the tests covering them
+    pub requirements: Vec<Requirement>,
+}
+
+/// What the template is rendered with
+#[derive(Serialize)]
+struct Context<'a> {
+    metadata: &'a Metadata,
+    config: &'a Config,
+    /// the content extracted for each section
+    sections: &'a IndexMap<String, String>,
+    /// the content of the named fragments of each section
+    fragments: &'a IndexMap<String, HashMap<String, String>>,
+    /// the normative requirements of the specification
+    requirements: &'a [Requirement],
+    /// the features enabled in the edition being built, and its profile
+    features: &'a BTreeSet<String>,
+    profile: Option<&'a str>,
 }
 
-/// Builds the specification and returns a number of files to watch
+/// Builds the specification in a format (at `output_file`, or at the default path of the format)
+/// and returns a number of files to watch
 pub fn build(
     toml_spec: PathBuf,
     output_file: Option<PathBuf>,
     output_format: OutputFormat,
 ) -> Result<HashSet<PathBuf>> {
-    let mut files_to_watch = HashSet::new();
+    let target = Target {
+        path: output_file,
+        ..Target::new(output_format)
+    };
+    build_targets(toml_spec, &[target])
+}
+
+/// Builds the outputs of the specification (the `targets`, or the outputs listed in the specification file)
+/// and returns a number of files to watch
+pub fn build_targets(toml_spec: PathBuf, targets: &[Target]) -> Result<HashSet<PathBuf>> {
+    let mut all_dependencies = HashSet::new();
+    for (path, output) in render_targets(&toml_spec, targets)? {
+        let Output {
+            files,
+            dependencies,
+            warnings,
+            ..
+        } = output;
+
+        for warning in warnings {
+            eprintln!("{warning:?}");
+        }
+
+        write_files(&files)?;
+        println!("\n=> specification saved at {}", path.display());
+        all_dependencies.extend(dependencies);
+    }
+
+    Ok(all_dependencies)
+}
+
+/// Builds the specification from a `build.rs` script,
+/// and tells cargo to run the script again when any file the specification is built from changes.
+/// Unless `strict` is set, problems are reported as cargo warnings instead of failing the build.
+pub fn build_script(
+    toml_spec: impl AsRef<Path>,
+    output_file: Option<PathBuf>,
+    output_format: OutputFormat,
+    strict: bool,
+) -> Result<()> {
+    let cargo_warning = |report: &Report| {
+        for line in format!("{report:?}").lines() {
+            println!("cargo:warning={line}");
+        }
+    };
+
+    // without any `rerun-if-changed`, cargo runs the script again on any change to the package
+    let output = match render_file(toml_spec.as_ref(), output_file, output_format) {
+        Ok(output) => output,
+        Err(report) if strict => return Err(report),
+        Err(report) => {
+            cargo_warning(&report);
+            return Ok(());
+        }
+    };
+
+    for dependency in &output.dependencies {
+        println!("cargo:rerun-if-changed={}", dependency.display());
+    }
+    for warning in &output.warnings {
+        cargo_warning(warning);
+    }
+
+    write_files(&output.files)
+}
+
+/// Writes the files of a specification (leaving the ones that didn't change untouched)
+fn write_files(files: &[OutputFile]) -> Result<()> {
+    for file in files {
+        if fs::read(&file.path).is_ok_and(|current| current == file.content) {
+            continue;
+        }
 
+        if let Some(dir) = file.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            fs::create_dir_all(dir)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("could not create directory {}", dir.display()))?;
+        }
+        fs::write(&file.path, &file.content)
+            .into_diagnostic()
+            .wrap_err_with(|| format!("could not write {}", file.path.display()))?;
+    }
+
+    Ok(())
+}
+
+/// Checks that the outputs of the specification (the `targets`, or the outputs listed in the specification file)
+/// are up to date, and prints how they differ from what would be built otherwise.
+pub fn check(toml_spec: PathBuf, targets: &[Target]) -> Result<()> {
+    let files: Vec<_> = render_targets(&toml_spec, targets)?
+        .into_iter()
+        .flat_map(|(_, output)| output.files)
+        .collect();
+
+    let mut outdated = vec![];
+    for file in &files {
+        let path = file.path.display().to_string();
+        let current = match fs::read(&file.path) {
+            Ok(current) if current == file.content => continue,
+            Ok(current) => current,
+            Err(_) => {
+                println!("missing file {path}");
+                outdated.push(path);
+                continue;
+            }
+        };
+
+        match (
+            std::str::from_utf8(&current),
+            std::str::from_utf8(&file.content),
+        ) {
+            (Ok(current), Ok(expected)) => print!(
+                "{}",
+                TextDiff::from_lines(current, expected)
+                    .unified_diff()
+                    .header(&format!("a/{path}"), &format!("b/{path}"))
+            ),
+            _ => println!("binary file {path} differs"),
+        }
+        outdated.push(path);
+    }
+
+    if !outdated.is_empty() {
+        return Err(SpecError::OutdatedSpecification(outdated.join(", ")).into());
+    }
+
+    let paths: Vec<_> = files
+        .iter()
+        .map(|file| file.path.display().to_string())
+        .collect();
+    println!("=> specification at {} is up to date", paths.join(", "));
+    Ok(())
+}
+
+/// Writes the traceability matrix between the requirements and the tests,
+/// as a markdown table (at `output_file`) and as JSON (at `json_file`).
+/// If `strict` is set, fails if a requirement isn't covered by any test.
+pub fn trace(
+    toml_spec: PathBuf,
+    output_file: Option<PathBuf>,
+    json_file: Option<PathBuf>,
+    strict: bool,
+    edition: &Edition,
+) -> Result<()> {
+    let Output {
+        warnings,
+        requirements,
+        ..
+    } = render_file_edition(&toml_spec, None, OutputFormat::Markdown, edition)?;
+
+    for warning in warnings {
+        eprintln!("{warning:?}");
+    }
+
+    let matrix = Matrix::new(&requirements);
+    let output_file = output_file.unwrap_or_else(|| PathBuf::from("traceability.md"));
+    let json_file = json_file.unwrap_or_else(|| PathBuf::from("traceability.json"));
+    write_files(&[
+        OutputFile::new(output_file.clone(), matrix.to_markdown()),
+        OutputFile::new(json_file.clone(), matrix.to_json()),
+    ])?;
+    println!(
+        "=> traceability matrix saved at {} and {}",
+        output_file.display(),
+        json_file.display()
+    );
+
+    if !matrix.uncovered.is_empty() {
+        let uncovered = matrix.uncovered.join(", ");
+        if strict {
+            return Err(SpecError::UncoveredRequirements(uncovered).into());
+        }
+        println!("=> requirements not covered by any test: {uncovered}");
+    }
+
+    Ok(())
+}
+
+/// Builds the specification described by the `toml_spec` specification file in memory,
+/// without writing anything.
+pub fn render_file(
+    toml_spec: &Path,
+    output_file: Option<PathBuf>,
+    output_format: OutputFormat,
+) -> Result<Output> {
+    render_file_edition(toml_spec, output_file, output_format, &Edition::default())
+}
+
+/// Builds an edition of the specification described by the `toml_spec` specification file in memory,
+/// without writing anything.
+pub fn render_file_edition(
+    toml_spec: &Path,
+    output_file: Option<PathBuf>,
+    output_format: OutputFormat,
+    edition: &Edition,
+) -> Result<Output> {
+    let target = Target {
+        path: output_file,
+        profile: edition.profile.clone(),
+        features: edition.features.clone(),
+        ..Target::new(output_format)
+    };
+    let mut outputs = render_targets(toml_spec, &[target])?;
+    Ok(outputs.remove(0).1)
+}
+
+/// Builds the outputs of the specification described by the `toml_spec` specification file in memory,
+/// without writing anything, and returns them along with where they are written.
+/// The outputs are the `targets` if there are any, or else the outputs listed in the specification file.
+/// Outputs built with the same features share what is extracted from the files of the specification.
+pub fn render_targets(toml_spec: &Path, targets: &[Target]) -> Result<Vec<(PathBuf, Output)>> {
     //~ 1. parse the specification file with the [toml_parser](#toml-parser)
-    let mut specification = toml_parser::parse_toml_spec(toml_spec.as_path())?;
+    let (specification, manifest) = toml_parser::parse_manifest(toml_spec)?;
+
+    let spec_dir = fs::canonicalize(toml_spec)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("could not find {}", toml_spec.display()))?
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let manifest_path = spec_dir.join(toml_spec.file_name().unwrap_or_default());
+
+    //~    and list the outputs to build: the ones given on the command line, if any,
+    //~    or else the ones listed in the `[[outputs]]` of the specification file
+    //~    (or markdown, if there are none).
+    //~    Paths listed in the specification file are relative to it.
+    let targets = if !targets.is_empty() {
+        targets.to_vec()
+    } else if !specification.outputs.is_empty() {
+        let manifest_dir = toml_spec.parent().unwrap_or_else(|| Path::new(""));
+        let mut targets = vec![];
+        for (index, target) in specification.outputs.iter().enumerate() {
+            if let Err(e) = target.edition().features(&specification) {
+                let profile = target.profile.as_deref().unwrap_or_default();
+                return Err(manifest.entry_error(&format!("outputs.{index}.profile"), profile, e));
+            }
+            targets.push(Target {
+                path: Some(manifest_dir.join(target.path())),
+                ..target.clone()
+            });
+        }
+        targets
+    } else {
+        vec![Target::new(OutputFormat::Markdown)]
+    };
+
+    let mut paths = HashSet::new();
+    for target in &targets {
+        if !paths.insert(target.path()) {
+            return Err(SpecError::DuplicateOutput(target.path()).into());
+        }
+    }
+
+    //~    The files of the specification are parsed once for each edition,
+    //~    and the outputs of the same edition are built from what was extracted.
+    let mut extractions: Vec<(Edition, Extraction)> = vec![];
+    let mut outputs = vec![];
+    for target in targets {
+        let edition = target.edition();
+        let (extraction, mut warnings) =
+            match extractions.iter().position(|(built, _)| *built == edition) {
+                Some(index) => (&extractions[index].1, vec![]),
+                None => {
+                    let mut extraction =
+                        extract(&specification, &spec_dir, Some(&manifest), &edition)?;
+                    let warnings = std::mem::take(&mut extraction.warnings);
+                    extractions.push((edition, extraction));
+                    (&extractions[extractions.len() - 1].1, warnings)
+                }
+            };
+
+        let config = Config {
+            numbering: target.numbering.unwrap_or(specification.config.numbering),
+            toc: target.toc.unwrap_or(specification.config.toc),
+            ..specification.config.clone()
+        };
+        let path = target.path();
+        let mut output = render_extraction(
+            &specification,
+            Some(&manifest),
+            &config,
+            extraction,
+            path.clone(),
+            target.format,
+        )?;
+        output.dependencies.insert(manifest_path.clone());
+        warnings.append(&mut output.warnings);
+        output.warnings = warnings;
+        outputs.push((path, output));
+    }
+
+    Ok(outputs)
+}
+
+/// Builds a specification in memory, without writing anything.
+/// The paths of the specification are relative to `spec_dir`,
+/// and the files produced are placed at `output_file` (or at the default path of the output format).
+pub fn render(
+    specification: &Specification,
+    spec_dir: &Path,
+    output_file: Option<PathBuf>,
+    output_format: OutputFormat,
+) -> Result<Output> {
+    render_edition(
+        specification,
+        spec_dir,
+        output_file,
+        output_format,
+        &Edition::default(),
+    )
+}
+
+/// Builds an edition of a specification in memory, like [render].
+pub fn render_edition(
+    specification: &Specification,
+    spec_dir: &Path,
+    output_file: Option<PathBuf>,
+    output_format: OutputFormat,
+    edition: &Edition,
+) -> Result<Output> {
+    let mut extraction = extract(specification, spec_dir, None, edition)?;
+    let mut warnings = std::mem::take(&mut extraction.warnings);
+    let mut output = render_extraction(
+        specification,
+        None,
+        &specification.config,
+        &extraction,
+        output_file.unwrap_or_else(|| output_format.default_output()),
+        output_format,
+    )?;
+    warnings.append(&mut output.warnings);
+    output.warnings = warnings;
+    Ok(output)
+}
+
+/// What is extracted from the files of a specification, for an edition
+struct Extraction {
+    template_path: PathBuf,
+    features: Features,
+    /// the content extracted for each section
+    sections: IndexMap<String, String>,
+    /// the content of the named fragments of each section
+    fragments: IndexMap<String, HashMap<String, String>>,
+    /// the blocks each section was extracted from
+    blocks: IndexMap<String, Vec<Block>>,
+    requirements: Vec<Requirement>,
+    /// the files read (the template, the files of each section, etc.)
+    dependencies: HashSet<PathBuf>,
+    warnings: Vec<Report>,
+}
+
+/// Extracts the content of an edition of a specification.
+/// Errors caused by an entry of the specification file point to it, if there's a `manifest`.
+fn extract(
+    specification: &Specification,
+    spec_dir: &Path,
+    manifest: Option<&Manifest>,
+    edition: &Edition,
+) -> Result<Extraction> {
+    let in_manifest = |key: &str, entry: &str, error: Report| match manifest {
+        Some(manifest) => manifest.entry_error(key, entry, error),
+        None => error,
+    };
+    let mut files_to_watch = HashSet::new();
 
-    let mut spec_dir =
-        fs::canonicalize(&toml_spec).expect("couldn't canonicalize the specification path");
-    spec_dir.pop();
+    //~    For each edition, the features it enables are collected (see [conditionals](#conditionals)).
+    let features = edition.features(specification)?;
 
     //~ 2. retrieve the template file
-    let mut template_path = spec_dir.clone();
-    template_path.push(&specification.config.template);
+    let template = &specification.config.template;
+    let template_path = spec_dir.join(template);
+    if !template_path.is_file() {
+        let error = SpecError::MissingTemplate(template_path).into();
+        return Err(in_manifest("config.template", template, error));
+    }
     files_to_watch.insert(template_path.clone());
 
-    let template = fs::read_to_string(&template_path)
-        .into_diagnostic()
-        .wrap_err_with(|| format!("could not read template {}", template_path.display(),))?;
-
     //~ 3. extract the spec comments from all the files listed using [comment_parser](#comment-parser)
-    let base = get_local_repo_path();
-    for filename in specification.sections.values_mut() {
-        let path = if matches!(filename.chars().next(), Some('@')) {
-            let base = base
-                .as_ref()
-                .ok_or(SpecError::NotGitRepo(filename.clone()))
-                .into_diagnostic()?;
-            let base = base.trim();
-            // TODO: this will panic if we just wrote @ and not @/
-            let filename = filename.split_at(2).1.to_string();
-            PathBuf::from(base).join(filename)
-        } else {
-            let mut path = spec_dir.clone();
-            path.push(&filename);
-            path
-        };
-        files_to_watch.insert(path.clone());
+    //~    (or from the Rust items listed, using the [rust_parser](#rust-parser)).
+    //~    A section can list several files and glob patterns,
+    //~    in which case the content extracted from each file is concatenated in order
+    //~    (files matched by a glob pattern are ordered by path).
+    //~    Named fragments are collected the same way, for each section,
+    //~    and [requirements](#requirements) for the whole specification.
+    let base = get_local_repo_path(spec_dir);
+    let mut sections = IndexMap::new();
+    let mut fragments = IndexMap::new();
+    let mut blocks = IndexMap::new();
+    let mut all_requirements = vec![];
+    let mut all_tests = vec![];
+    let mut parsed_files = HashSet::new();
+    let mut warnings = vec![];
+    for (name, section) in &specification.sections {
+        let key = format!("sections.{name}");
+        let mut contents = vec![];
+        let mut section_fragments = HashMap::new();
+        let mut section_blocks = vec![];
+        let mut included = HashSet::new();
 
-        *filename = comment_parser::parse_file(&path)?;
+        //~    In `docs` mode, a section can list `items` (e.g. `items = ["crate::api::Client"]`)
+        //~    to only extract the doc comments of these items (and all the spec comments),
+        //~    which are found by their path in the crate like [Rust items](#rust-parser)
+        //~    and have to be defined in the files of the section.
+        let canonical = |path: &Path| fs::canonicalize(path).unwrap_or_else(|_| path.to_path_buf());
+        let mut doc_items = vec![];
+        let mut doc_lines: HashMap<PathBuf, HashSet<usize>> = HashMap::new();
+        for item_path in section.items() {
+            let found = rust_parser::find_item(spec_dir, item_path.trim())
+                .map_err(|e| in_manifest(&key, item_path, e))?;
+            files_to_watch.extend(found.files_read.iter().cloned());
+            let file = canonical(&found.file);
+            doc_lines
+                .entry(file.clone())
+                .or_default()
+                .extend(found.item.doc_lines());
+            doc_items.push((item_path, file));
+        }
+        let no_doc_lines = HashSet::new();
+
+        for entry in section.entries() {
+            if let Some(item_path) = entry.strip_prefix("item:") {
+                let found = rust_parser::find_item(spec_dir, item_path.trim())
+                    .map_err(|e| in_manifest(&key, entry, e))?;
+                files_to_watch.extend(found.files_read.iter().cloned());
+                contents.push(found.to_markdown());
+                section_blocks.extend(found.blocks());
+                continue;
+            }
+
+            let paths = resolve_files(name, entry, spec_dir, base.as_deref())
+                .map_err(|e| in_manifest(&key, entry, e))?;
+            for path in paths {
+                if included.insert(path.clone()) {
+                    files_to_watch.insert(path.clone());
+                    let file_doc_lines = (!doc_items.is_empty())
+                        .then(|| doc_lines.get(&canonical(&path)).unwrap_or(&no_doc_lines));
+                    let parsed = comment_parser::parse_file(
+                        &path,
+                        &specification.languages,
+                        section.mode(),
+                        file_doc_lines,
+                        &features,
+                    )
+                    .map_err(|e| in_manifest(&key, entry, e))?;
+                    contents.push(parsed.content);
+                    section_blocks.extend(parsed.blocks);
+
+                    // a file can be part of several sections, but it registers its requirements once
+                    if parsed_files.insert(path.clone()) {
+                        all_requirements.extend(parsed.requirements);
+                        all_tests.extend(parsed.tests);
+                        warnings.extend(parsed.warnings);
+                    }
+                    for (fragment, content) in parsed.fragments {
+                        section_fragments
+                            .entry(fragment)
+                            .or_insert_with(Vec::new)
+                            .push(content);
+                    }
+                }
+            }
+        }
+
+        let included: HashSet<_> = included.iter().map(|path| canonical(path)).collect();
+        if let Some((item_path, _)) = doc_items.iter().find(|(_, file)| !included.contains(file)) {
+            let error = SpecError::ItemOutsideSection(item_path.to_string(), name.clone());
+            return Err(in_manifest(&key, item_path, error.into()));
+        }
+
+        sections.insert(name.clone(), contents.join("\n"));
+        blocks.insert(name.clone(), section_blocks);
+        fragments.insert(
+            name.clone(),
+            section_fragments
+                .into_iter()
+                .map(|(fragment, contents)| (fragment, contents.join("\n")))
+                .collect(),
+        );
     }
 
-    //~ 4. render the template
-    let mut tt = TinyTemplate::new();
-    tt.set_default_formatter(&tinytemplate::format_unescaped);
-    tt.add_template("specification", &template)
-        .into_diagnostic()
-        .wrap_err_with(|| format!("can't parse template {}", template_path.display(),))?;
+    //~    Tests covering requirements are also collected from the files listed by `tests` in the config
+    //~    (see [traceability](#traceability)).
+    for entry in &specification.config.tests {
+        let paths = resolve_files("config.tests", entry, spec_dir, base.as_deref())
+            .map_err(|e| in_manifest("config.tests", entry, e))?;
+        for path in paths {
+            if parsed_files.insert(path.clone()) {
+                files_to_watch.insert(path.clone());
+                let parsed = comment_parser::parse_file(
+                    &path,
+                    &specification.languages,
+                    SectionMode::Spec,
+                    None,
+                    &features,
+                )
+                .map_err(|e| in_manifest("config.tests", entry, e))?;
+                all_requirements.extend(parsed.requirements);
+                all_tests.extend(parsed.tests);
+                warnings.extend(parsed.warnings);
+            }
+        }
+    }
 
-    let rendered = tt
-        .render("specification", &specification)
-        .into_diagnostic()
-        .wrap_err_with(|| {
-            format!(
-                "template file can't be rendered: {}",
-                template_path.display()
-            )
-        })?;
+    requirements::check_duplicates(&all_requirements)?;
+    traceability::link(&mut all_requirements, all_tests)?;
+
+    // paths are shown relative to the specification file
+    let relative = |path: &mut PathBuf| {
+        if let Ok(relative) = path.strip_prefix(spec_dir) {
+            *path = relative.to_path_buf();
+        }
+    };
+    for block in blocks.values_mut().flatten() {
+        relative(&mut block.file);
+    }
+    for requirement in &mut all_requirements {
+        relative(&mut requirement.file);
+        for test in &mut requirement.tests {
+            relative(&mut test.file);
+        }
+    }
+
+    Ok(Extraction {
+        template_path,
+        features,
+        sections,
+        fragments,
+        blocks,
+        requirements: all_requirements,
+        dependencies: files_to_watch,
+        warnings,
+    })
+}
 
-    //~ 5. build the spec. We currently support two different formats:
+/// Builds a specification in memory from what was extracted for its edition,
+/// with `config` rather than the config of the specification (to override its options).
+/// Warnings about the specification file point to it, if there's a `manifest`.
+fn render_extraction(
+    specification: &Specification,
+    manifest: Option<&Manifest>,
+    config: &Config,
+    extraction: &Extraction,
+    output: PathBuf,
+    output_format: OutputFormat,
+) -> Result<Output> {
+    let mut files_to_watch = extraction.dependencies.clone();
+    let mut warnings = vec![];
+    let context = Context {
+        metadata: &specification.metadata,
+        config,
+        sections: &extraction.sections,
+        fragments: &extraction.fragments,
+        requirements: &extraction.requirements,
+        features: &extraction.features.enabled,
+        profile: extraction.features.profile.as_deref(),
+    };
+
+    //~ 4. check the placeholders of the [template](#template), and render it
+    let (rendered, templates, unused_sections) = template::render(
+        &extraction.template_path,
+        &context,
+        specification.sections.keys(),
+    )?;
+    files_to_watch.extend(templates);
+
+    // unused sections are pointed to in the specification file, if there's one
+    warnings.extend(unused_sections.into_iter().map(|section| {
+        let key = format!("sections.{section}");
+        match manifest.and_then(|manifest| Some((manifest, manifest.span(&key)?))) {
+            Some((manifest, bad_bit)) => {
+                Report::from(SpecError::UnusedSection { section, bad_bit })
+                    .with_source_code(manifest.source())
+            }
+            None => SpecError::UnusedSection {
+                section,
+                bad_bit: (0, 0),
+            }
+            .into(),
+        }
+    }));
+
+    //~ 5. number the headers and insert a table of contents, if enabled (see [outline](#outline)).
+    //~    The typst and PDF formats always number their headers and have a table of contents.
+    //~    The html format always has a table of contents (in its sidebar), so none is inserted in the page.
+    let (rendered, aliases) = match output_format {
+        OutputFormat::Typst | OutputFormat::Pdf => (rendered, HashMap::new()),
+        OutputFormat::Html => outline::outline(&rendered, config.numbering, false),
+        _ => outline::outline(&rendered, config.numbering, config.toc),
+    };
+
+    //~ 6. replace the [references](#references) with links
+    let rendered = references::resolve(&rendered, &aliases, &files_to_watch)?;
+
+    //~ 7. build the spec. We currently support these formats:
     use OutputFormat::*;
-    match output_format {
+    let files = match output_format {
         //~     - [markdown](https://daringfireball.net/projects/markdown/)
-        Markdown => formats::markdown::build(&rendered, output_file),
+        Markdown => vec![OutputFile::new(output, rendered)],
         //~     - [respec](https://github.com/w3c/respec/)
-        Respec => {
-            formats::respec::build(&specification, &rendered, output_file);
-        }
+        Respec => vec![OutputFile::new(
+            output,
+            formats::respec::to_respec(specification, &rendered),
+        )],
+        //~     - html, a standalone page that doesn't load anything from the network
+        Html => vec![OutputFile::new(
+            output,
+            formats::html::to_html(specification, &rendered),
+        )],
+        //~     - [typst](https://typst.app/), and PDF (produced with typst)
+        Typst => vec![OutputFile::new(
+            output,
+            formats::typst::to_typst(specification, &rendered),
+        )],
+        Pdf => vec![OutputFile::new(
+            output,
+            formats::typst::to_pdf(specification, &rendered)?,
+        )],
+        //~     - [mdbook](https://rust-lang.github.io/mdBook/), split in one chapter per top-level section
+        Mdbook => formats::mdbook::to_mdbook(specification, &rendered, &output),
+        //~     - JSON, with what was extracted for each section (and where it comes from)
+        //~       rather than the rendered specification
+        Json => vec![OutputFile::new(
+            output,
+            formats::json::to_json(
+                &specification.metadata,
+                &extraction.sections,
+                &extraction.fragments,
+                &extraction.blocks,
+                &extraction.requirements,
+            ),
+        )],
     };
 
-    // return a number of files to watch (useful for the [watch] function)
-    Ok(files_to_watch)
+    Ok(Output {
+        files,
+        dependencies: files_to_watch,
+        warnings,
+        requirements: extraction.requirements.clone(),
+    })
 }
 
+/// Returns the files an entry of a section refers to.
+/// Paths are relative to the specification file, or to the root of the git repository if they start with `@/`,
+/// and can be glob patterns.
+fn resolve_files(
+    section: &str,
+    entry: &str,
+    spec_dir: &Path,
+    base: Option<&str>,
+) -> Result<Vec<PathBuf>> {
+    let (dir, relative) = if let Some(filename) = entry.strip_prefix('@') {
+        let base = base.ok_or_else(|| SpecError::NotGitRepo(section.to_string()))?;
+        (PathBuf::from(base.trim()), filename.trim_start_matches('/'))
+    } else {
+        (spec_dir.to_path_buf(), entry)
+    };
+
+    if !relative.contains(['*', '?', '[']) {
+        return Ok(vec![dir.join(relative)]);
+    }
+
+    // only the entry is a pattern, not the directory it's relative to (which can contain `[`, `*`, etc.)
+    let bad_pattern = |e: String| SpecError::BadPattern(entry.to_string(), section.to_string(), e);
+    let dir = dir
+        .to_str()
+        .ok_or_else(|| bad_pattern("the path is not valid UTF-8".to_string()))?;
+    let pattern = Path::new(&glob::Pattern::escape(dir)).join(relative);
+    let pattern = pattern.to_str().unwrap_or_default();
+    let mut files = glob::glob(pattern)
+        .map_err(|e| bad_pattern(e.to_string()))?
+        .filter_map(|path| path.ok())
+        .filter(|path| path.is_file())
+        .collect::<Vec<_>>();
+    files.sort();
+
+    if files.is_empty() {
+        return Err(SpecError::NoMatchingFile(entry.to_string(), section.to_string()).into());
+    }
+
+    Ok(files)
+}
+
+/// Builds the specification in a format (at `output_file`, or at the default path of the format)
+/// every time one of the files it's built from changes
 pub fn watch(toml_spec: PathBuf, output_format: OutputFormat, output_file: Option<PathBuf>) {
+    let target = Target {
+        path: output_file,
+        ..Target::new(output_format)
+    };
+    watch_targets(toml_spec, &[target])
+}
+
+/// Builds the outputs of the specification (the `targets`, or the outputs listed in the specification file)
+/// every time one of the files they're built from changes
+pub fn watch_targets(toml_spec: PathBuf, targets: &[Target]) {
     use notify::{watcher, RecursiveMode, Watcher};
     use std::sync::mpsc::channel;
     use std::time::Duration;
@@ -121,13 +820,13 @@ pub fn watch(toml_spec: PathBuf, output_format: OutputFormat, output_file: Optio
 
     loop {
         // build and get files to watch
-        match build(toml_spec.clone(), output_file.clone(), output_format) {
+        match build_targets(toml_spec.clone(), targets) {
             Err(e) => println!("error: {}", e),
             Ok(new_files_to_watch) => {
                 // watch any new files contained in the specification
                 for file in new_files_to_watch.difference(&files_to_watch) {
                     watcher
-                        .watch(&file, RecursiveMode::NonRecursive)
+                        .watch(file, RecursiveMode::NonRecursive)
                         .unwrap_or_else(|_e| {
                             panic!("could not find file to watch {}", file.display())
                         });
@@ -135,7 +834,7 @@ pub fn watch(toml_spec: PathBuf, output_format: OutputFormat, output_file: Optio
 
                 // unwatch files that are not in the specification
                 for file in files_to_watch.difference(&new_files_to_watch) {
-                    watcher.unwatch(&file).unwrap_or_else(|_e| {
+                    watcher.unwatch(file).unwrap_or_else(|_e| {
                         panic!("could not find file to watch {}", file.display())
                     });
                 }
@@ -1,38 +1,233 @@
+use std::cell::OnceCell;
+use std::collections::{HashMap, HashSet};
 use std::fmt::Write as FmtWrite;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use miette::{IntoDiagnostic, NamedSource, Result, WrapErr};
+use miette::{IntoDiagnostic, NamedSource, Report, Result, WrapErr};
+use serde::Serialize;
 
-use crate::errors::SpecError;
+use crate::{
+    errors::SpecError,
+    features::{self, Features},
+    formats::CodeOptions,
+    git::Repository,
+    languages,
+    requirements::{self, Requirement},
+    rust_parser::RustItems,
+    rustdoc::{self, DocComments},
+    template::did_you_mean,
+    toml_parser::{Language, SectionMode},
+    traceability::{self, Test, TestLink},
+};
 
 /// The prefix to any spec instructions
 const SPECIFICATION_INSTRUCTION: &str = "spec:";
 
-/// Parse a file and return the specification-related content
-pub fn parse_file(file_name: &Path) -> Result<String> {
+/// The instructions that can follow the prefix
+const INSTRUCTIONS: [&str; 12] = [
+    "startcode",
+    "endcode",
+    "hide",
+    "show",
+    "if",
+    "endif",
+    "item",
+    "begin",
+    "end",
+    "anchor",
+    "req",
+    "tests",
+];
+
+/// The specification-related content of a file
+#[derive(Debug, Default)]
+pub struct ParsedFile {
+    /// everything that was extracted from the file
+    pub content: String,
+    /// the content of each named fragment (`spec:begin(name)` to `spec:end(name)`)
+    pub fragments: HashMap<String, String>,
+    /// what was extracted, split in blocks of prose, code, and instructions
+    pub blocks: Vec<Block>,
+    /// the requirements registered in the file (`spec:req(ID)`)
+    pub requirements: Vec<Requirement>,
+    /// the tests of the file that cover requirements (`spec:tests(ID)`)
+    pub tests: Vec<TestLink>,
+    /// problems that don't prevent the file from being used
+    pub warnings: Vec<Report>,
+}
+
+/// What a block of extracted content is made of
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum BlockKind {
+    /// spec comments (or the content of a markdown file)
+    Prose,
+    /// code extracted with `spec:startcode` or `spec:item`
+    Code,
+    /// a `spec:` instruction
+    Instruction,
+}
+
+/// A block of extracted content, along with where it was extracted from
+#[derive(Serialize, Debug, Clone)]
+pub struct Block {
+    pub kind: BlockKind,
+    pub file: PathBuf,
+    /// the first line the block was extracted from (starting at 1)
+    pub first_line: usize,
+    /// the last line the block was extracted from
+    pub last_line: usize,
+    /// the extracted text (without code fences, for code)
+    pub content: String,
+}
+
+/// Adds a line to the last block if it has the same kind and the line follows it,
+/// or starts a new block otherwise (each instruction is a block of its own)
+fn push_line(blocks: &mut Vec<Block>, kind: BlockKind, file: &Path, line: usize, content: &str) {
+    match blocks.last_mut() {
+        Some(last)
+            if last.kind == kind
+                && kind != BlockKind::Instruction
+                && last.last_line + 1 == line =>
+        {
+            last.last_line = line;
+        }
+        _ => blocks.push(Block {
+            kind,
+            file: file.to_path_buf(),
+            first_line: line,
+            last_line: line,
+            content: String::new(),
+        }),
+    }
+
+    let last = blocks.last_mut().unwrap();
+    last.content.push_str(content);
+    last.content.push('\n');
+}
+
+/// Parse a file and return the specification-related content.
+/// In `docs` mode, only the doc comments on `doc_lines` (starting at 1) are extracted, if it's set.
+pub fn parse_file(
+    file_name: &Path,
+    languages: &HashMap<String, Language>,
+    mode: SectionMode,
+    doc_lines: Option<&HashSet<usize>>,
+    features: &Features,
+) -> Result<ParsedFile> {
     //~ parsing is based on the extension of the file:
     match Path::new(file_name)
         .extension()
+        .and_then(|extension| extension.to_str())
         .ok_or_else(|| SpecError::CantParseFile(file_name.to_path_buf()))?
-        .to_str()
-        .expect("couldn't convert the extension to a string")
     {
         //~ - for markdown files, we retrieve the entire content
-        "md" => std::fs::read_to_string(file_name)
-            .into_diagnostic()
-            .wrap_err_with(|| format!("could not read file {}", file_name.display())),
+        "md" => {
+            let content = std::fs::read_to_string(file_name)
+                .into_diagnostic()
+                .wrap_err_with(|| format!("could not read file {}", file_name.display()))?;
+            let blocks = vec![Block {
+                kind: BlockKind::Prose,
+                file: file_name.to_path_buf(),
+                first_line: 1,
+                last_line: content.lines().count(),
+                content: content.clone(),
+            }];
+            Ok(ParsedFile {
+                content,
+                blocks,
+                ..Default::default()
+            })
+        }
+
+        //~ - for other files we look for comments starting with the comment delimiter
+        //~   of the language followed by a `~`
+        //~   (e.g. `//~` or `/*~ */` in Rust, `#~` in Python, `(*~ *)` in OCaml, `--~` in SQL)
+        //~   (see [languages](#languages)),
+        //~   as well as doc comments for Rust files in a section in `docs` mode (see [rustdoc](#rustdoc))
+        ext => parse_code(
+            &languages::find(ext, languages),
+            file_name,
+            mode == SectionMode::Docs && ext == "rs",
+            doc_lines,
+            features,
+        ),
+    }
+}
 
-        //~ - for python files we look for comments starting with `#~`
-        "py" => parse_code("python", "#~", None, file_name),
+/// writes a permalink to the given lines (starting at 1) of a file,
+/// if the file is in a GitHub or GitLab repository
+fn write_permalink(
+    result: &mut String,
+    repository: &OnceCell<Option<Repository>>,
+    file_name: &Path,
+    first_line: usize,
+    last_line: usize,
+) {
+    if last_line < first_line {
+        return;
+    }
 
-        //~ - for ML files we look for comments starting with `#~`
-        "ml" | "mli" => parse_code("ocaml", "(*~", Some("*)"), file_name),
+    let permalink = repository
+        .get_or_init(|| Repository::for_file(file_name))
+        .as_ref()
+        .and_then(|repo| repo.permalink(file_name, first_line, last_line));
 
-        //~ - for other files we look for comments starting with `//~`
-        ext => parse_code(ext, "//~", None, file_name),
+    if let Some(permalink) = permalink {
+        writeln!(result, "\n[source]({permalink})\n").unwrap();
     }
 }
 
+/// splits an instruction like `begin(handshake)` into its name and its argument
+fn parse_instruction(instruction: &str) -> (&str, Option<&str>) {
+    let instruction = instruction.trim();
+    let name_len = instruction
+        .find(|c: char| c == '(' || c.is_whitespace())
+        .unwrap_or(instruction.len());
+    let (name, rest) = instruction.split_at(name_len);
+    let argument = rest
+        .strip_prefix('(')
+        .and_then(|rest| rest.split_once(')'))
+        .map(|(argument, _)| argument.trim());
+    (name, argument)
+}
+
+/// returns the name of a fragment if it is valid
+/// (fragment names are used in templates, so they can only contain letters, digits, and underscores)
+fn fragment_name(argument: Option<&str>) -> Option<&str> {
+    argument.filter(|name| {
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+/// Returns the name of an anchor, if it's valid
+fn anchor_name(argument: Option<&str>) -> Option<&str> {
+    argument.filter(|name| {
+        !name.is_empty()
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    })
+}
+
+/// removes the indentation the lines of code have in common from a line of code
+/// (blank lines, which can have less of it, become empty)
+fn dedent<'a>(line: &'a str, indent: &str) -> &'a str {
+    line.strip_prefix(indent)
+        .unwrap_or_else(|| line.trim_start())
+}
+
+/// returns the longest prefix two strings have in common, compared character by character
+/// (so that indentations mixing tabs and spaces are only matched if they're the same)
+fn common_prefix<'a>(a: &'a str, b: &str) -> &'a str {
+    let len = a
+        .char_indices()
+        .zip(b.chars())
+        .find(|((_, a), b)| a != b)
+        .map_or(a.len().min(b.len()), |((offset, _), _)| offset);
+    &a[..len]
+}
+
 /// detects if a comment ends on this same line
 fn has_end(end: &str, comment: &str) -> bool {
     comment.trim().ends_with(end)
@@ -43,18 +238,90 @@ fn has_end(end: &str, comment: &str) -> bool {
 //~
 
 /// Parse code to return the specification-related content
-/// (comments that start with a special delimiter, by default `~`)
+/// (comments that start with a special delimiter, by default `~`, and doc comments if `doc_comments` is set,
+/// only the ones on `doc_lines` if it's set),
+/// leaving out what is conditioned on features that are not enabled
 pub fn parse_code(
-    lang: &str,
-    start_comment: &str,
-    end_comment: Option<&str>,
+    language: &Language,
     file_name: &Path,
-) -> Result<String> {
+    doc_comments: bool,
+    doc_lines: Option<&HashSet<usize>>,
+    features: &Features,
+) -> Result<ParsedFile> {
+    let lang = language.fence.as_deref().unwrap_or_default();
+
+    // the start delimiters of spec comments, along with their end delimiter for block comments
+    // (longest first, in case a delimiter is a prefix of another one)
+    let mut delimiters: Vec<(String, Option<&str>)> = vec![];
+    if let Some(line_comment) = &language.line_comment {
+        delimiters.push((format!("{line_comment}~"), None));
+    }
+    if let Some((start, end)) = &language.block_comment {
+        delimiters.push((format!("{start}~"), Some(end.as_str())));
+    }
+    delimiters.sort_by_key(|(start, _)| std::cmp::Reverse(start.len()));
+    let is_spec_comment = |line: &str| {
+        delimiters
+            .iter()
+            .any(|(start, _)| line.trim_start().starts_with(start.as_str()))
+    };
+
     // set to the offset of the startcode if we're waiting for an endcode instruction
     let mut extract_code = None;
 
-    // set to the indentation of the 1st line if we're within a multi-line in a comment
-    let mut in_spec_comment = None;
+    // the `spec:if` conditions we're in, with the offset of their instruction, if they're met,
+    // and the startcode that was open when they started
+    let mut conditions: Vec<(usize, bool, Option<usize>)> = vec![];
+    // the startcode that would be open, in what is skipped because a condition isn't met
+    let mut skipped_code = None;
+
+    // set if we're hiding lines of the code being extracted,
+    // to the first hidden line (starting at 1) and the marker that replaces the hidden lines, once there's one
+    let mut hiding: Option<Option<(usize, String)>> = None;
+
+    // what hidden lines are replaced with
+    let elision = match (&language.line_comment, &language.block_comment) {
+        (Some(line_comment), _) => format!("{line_comment} ..."),
+        (None, Some((start, end))) => format!("{start} ... {end}"),
+        (None, None) => "...".to_string(),
+    };
+
+    // the first line (starting at 1) of the code being extracted, and its common indentation
+    let mut code_first_line = 0;
+    let mut code_indent = "";
+
+    // the git repository containing the file, used to link to extracted code
+    let repository = OnceCell::new();
+
+    // the items of the file, if it's a Rust file that uses `spec:item`
+    let mut rust_items = None;
+
+    // set to the indentation of the 1st line (and the end delimiter)
+    // if we're within a multi-line in a comment
+    let mut in_spec_comment: Option<(usize, &str)> = None;
+
+    // the named fragments that haven't been ended yet,
+    // with the offset of their begin instruction, where their content starts in `result`,
+    // and the code block they started in (if any)
+    let mut open_fragments: Vec<(&str, usize, usize, Option<usize>)> = vec![];
+
+    // the content of the named fragments
+    let mut fragments: HashMap<String, String> = HashMap::new();
+
+    // the requirements of the file, and if the spec comments are still part of the last one
+    let mut requirements: Vec<Requirement> = vec![];
+    let mut in_requirement = false;
+
+    // the tests of the file that cover requirements
+    let mut tests = vec![];
+
+    // if the spec comments are in a code block (in which RFC 2119 keywords are not checked)
+    let mut in_code_block = false;
+
+    let mut warnings = vec![];
+
+    // what was extracted, in blocks
+    let mut blocks = vec![];
 
     // to store the result of extracting doc comments
     let mut result = String::new();
@@ -64,14 +331,107 @@ pub fn parse_code(
         .into_diagnostic()
         .wrap_err_with(|| format!("could not read file {}", file_name.display()))?;
 
+    // converts the doc comments, if they are extracted,
+    // and the line (starting at 0) of the last doc comment
+    let mut docs = doc_comments.then(|| DocComments::new(&source));
+    let mut last_doc_line = None;
+
     // go over the file line by line
     let mut byte_offset_for_errors = 0;
-    for line in source.lines() {
+    for (line_number, line) in source.lines().enumerate() {
+        // the delimiter this line starts with, if it starts a spec comment
+        let delimiter = delimiters
+            .iter()
+            .find(|(start, _)| line.trim_start().starts_with(start.as_str()))
+            .filter(|_| in_spec_comment.is_none());
+
+        // what is conditioned on a feature that isn't enabled is skipped,
+        // except for the instructions that start and end conditions
+        // (and the ones that start and end code, to check that conditions don't straddle them)
+        if conditions.iter().any(|(_, met, _)| !met) {
+            let instruction = delimiter
+                .map(|(start, _)| line.split_once(start.as_str()).unwrap().1.trim())
+                .and_then(|comment| comment.strip_prefix(SPECIFICATION_INSTRUCTION))
+                .map(parse_instruction);
+            let offset = line
+                .find(SPECIFICATION_INSTRUCTION)
+                .map(|column| byte_offset_for_errors + column + SPECIFICATION_INSTRUCTION.len());
+            match (instruction, offset) {
+                (Some(("if", _)), Some(offset)) => {
+                    conditions.push((offset, false, skipped_code));
+                }
+                (Some(("endif", _)), Some(offset)) => {
+                    let (start, _, code) = conditions.pop().unwrap();
+                    if code != skipped_code {
+                        return Err(SpecError::IfAcrossCode {
+                            src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                            start: (start, "if".len()),
+                            end: (offset, "endif".len()),
+                        }
+                        .into());
+                    }
+                }
+                (Some(("startcode", _)), Some(offset)) => skipped_code = Some(offset),
+                (Some(("endcode", _)), _) => skipped_code = None,
+                _ => (),
+            }
+
+            byte_offset_for_errors += line.len() + 1; // +1 for the newline character
+            continue;
+        }
+
+        // doc comments are extracted as prose, unless they're part of extracted code
+        let doc_comment = rustdoc::doc_comment(line)
+            .filter(|_| in_spec_comment.is_none() && extract_code.is_none())
+            .filter(|_| doc_lines.is_none_or(|lines| lines.contains(&(line_number + 1))));
+        if let (Some(doc_comment), Some(docs)) = (doc_comment, &mut docs) {
+            in_requirement = false;
+            if let Some(text) = docs.line(doc_comment) {
+                // each run of doc comments is a paragraph of its own
+                if last_doc_line.map(|last| last + 1) != Some(line_number)
+                    && !result.is_empty()
+                    && !result.ends_with("\n\n")
+                {
+                    result.push('\n');
+                }
+                writeln!(&mut result, "{text}").unwrap();
+                push_line(
+                    &mut blocks,
+                    BlockKind::Prose,
+                    file_name,
+                    line_number + 1,
+                    &text,
+                );
+            }
+            last_doc_line = Some(line_number);
+
+            byte_offset_for_errors += line.len() + 1; // +1 for the newline character
+            continue;
+        }
+
         //~ 1. only print a normal line if it is between `//~ spec:startcode` and `//~spec:endcode` statements
-        if !line.trim_start().starts_with(start_comment) && in_spec_comment.is_none() {
+        //~    (or if it's a doc comment that is extracted, see [rustdoc](#rustdoc))
+        if delimiter.is_none() && in_spec_comment.is_none() {
+            in_requirement = false;
             if extract_code.is_some() {
-                // TODO: reset indentation
-                writeln!(&mut result, "{}", line).unwrap();
+                let code = dedent(line, code_indent);
+
+                // hidden lines are replaced by a marker, with the indentation of the first one
+                if let Some(hidden) = &mut hiding {
+                    if hidden.is_none() && !code.trim().is_empty() {
+                        let indent = &code[..code.len() - code.trim_start().len()];
+                        *hidden = Some((line_number + 1, format!("{indent}{elision}")));
+                    }
+                } else {
+                    writeln!(&mut result, "{}", code).unwrap();
+                    push_line(
+                        &mut blocks,
+                        BlockKind::Code,
+                        file_name,
+                        line_number + 1,
+                        code,
+                    );
+                }
             }
 
             byte_offset_for_errors += line.len() + 1; // +1 for the newline character
@@ -80,7 +440,7 @@ pub fn parse_code(
 
         //~ 2. if we are within a multi-line comment, we remove the indentation
         //~   based on the indentation of the first line of the comment
-        let comment = if let Some(indentation) = in_spec_comment {
+        let comment = if let Some((indentation, _)) = in_spec_comment {
             let left_trimmed = line.trim_start();
             let whitespaces_len = line.len() - left_trimmed.len();
             if indentation > whitespaces_len {
@@ -91,75 +451,476 @@ pub fn parse_code(
         } else {
             //~ 3. otherwise, we extract what comes after the comment delimiter
             //~   (note that the result might still have a starting space)
-            line.split_once(start_comment).unwrap().1
+            let (start_comment, _) = delimiter.unwrap();
+            line.split_once(start_comment.as_str()).unwrap().1
         };
 
         //~ 4. lines starting with `//~ spec:` are specific instructions:
         if in_spec_comment.is_none() && comment.trim().starts_with(SPECIFICATION_INSTRUCTION) {
-            let instruction = comment
-                // get part after spec:
-                .split_once(SPECIFICATION_INSTRUCTION)
-                .unwrap()
-                .1
-                // remove anything after the instruction
-                .split(' ')
-                .next()
-                .unwrap();
+            let (instruction, argument) =
+                parse_instruction(comment.split_once(SPECIFICATION_INSTRUCTION).unwrap().1);
+            in_requirement = false;
+            push_line(
+                &mut blocks,
+                BlockKind::Instruction,
+                file_name,
+                line_number + 1,
+                comment.trim(),
+            );
 
             match instruction {
                 //~~ - a comment starting with `//~ spec:startcode` will print
-                //~       every line afterwards, up until a `//~ spec:endcode` statement
+                //~       every line afterwards, up until a `//~ spec:endcode` statement.
+                //~~   The indentation that the lines have in common is removed,
+                //~~   and the code block can have options, separated by commas
+                //~~   (e.g. `//~ spec:startcode(lang=rust, linenos, highlight=3-5)`),
+                //~~   which are written after the language in the fence of the code block
+                //~~   (e.g. ```` ```rust linenos highlight=3-5 ````)
+                //~~   and are rendered by the respec and html formats:
+                //~~~ - `lang=name` changes the language of the code block
+                //~~~   (by default, the language of the file)
+                //~~~ - `linenos` numbers the lines of the code block
+                //~~~ - `highlight=3-5` highlights lines 3 to 5 of the code block (`highlight=3` only line 3),
+                //~~~   and can be given several times
                 "startcode" if extract_code.is_none() => {
                     let column = line.find("startcode").unwrap();
-                    writeln!(&mut result, "```{lang}").unwrap();
+                    let mut options =
+                        CodeOptions::parse(argument.unwrap_or_default()).map_err(|option| {
+                            let offset = column + line[column..].find(&option).unwrap();
+                            SpecError::BadCodeOption {
+                                src: NamedSource::new(
+                                    file_name.to_string_lossy(),
+                                    source.to_string(),
+                                ),
+                                bad_bit: (byte_offset_for_errors + offset, option.len()),
+                                option,
+                            }
+                        })?;
+                    options.lang.get_or_insert_with(|| lang.to_string());
+                    writeln!(&mut result, "```{}", options.info_string()).unwrap();
                     extract_code = Some(byte_offset_for_errors + column);
+                    code_first_line = line_number + 2;
+
+                    // the indentation that the lines of code have in common,
+                    // leaving out the lines skipped because a condition isn't met
+                    let mut met = vec![];
+                    code_indent = source
+                        .lines()
+                        .skip(line_number + 1)
+                        .take_while(|line| {
+                            !(is_spec_comment(line) && line.contains("spec:endcode"))
+                        })
+                        .filter(|line| {
+                            if !is_spec_comment(line) {
+                                return !line.trim().is_empty() && !met.contains(&false);
+                            }
+                            let instruction = line
+                                .split_once(SPECIFICATION_INSTRUCTION)
+                                .map(|(_, instruction)| parse_instruction(instruction));
+                            match instruction {
+                                Some(("if", argument)) => met.push(
+                                    features::condition(argument)
+                                        .is_some_and(|condition| features.is_met(condition)),
+                                ),
+                                Some(("endif", _)) => {
+                                    met.pop();
+                                }
+                                _ => (),
+                            }
+                            false
+                        })
+                        .map(|line| &line[..line.len() - line.trim_start().len()])
+                        .reduce(common_prefix)
+                        .unwrap_or_default();
                 }
                 "startcode" if extract_code.is_some() => {
                     let column = line.find("startcode").unwrap();
                     return Err(SpecError::DoubleStartcode {
                         src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
                         bad_bit: (byte_offset_for_errors + column, "startcode".len()),
-                    })
-                    .into_diagnostic();
+                    }
+                    .into());
                 }
                 // spec:endcode ends spec:startcode
+                //~~ - if the file is in a GitHub or GitLab repository,
+                //~~   the extracted code is followed by a permalink to its source
+                //~~   (unless the file has uncommitted changes)
                 "endcode" if extract_code.is_some() => {
+                    if let Some(Some((hidden_line, marker))) = hiding.take() {
+                        writeln!(&mut result, "{marker}").unwrap();
+                        push_line(
+                            &mut blocks,
+                            BlockKind::Code,
+                            file_name,
+                            hidden_line,
+                            &marker,
+                        );
+                    }
                     writeln!(&mut result, "```").unwrap();
                     extract_code = None;
+
+                    let code_last_line = line_number;
+                    write_permalink(
+                        &mut result,
+                        &repository,
+                        file_name,
+                        code_first_line,
+                        code_last_line,
+                    );
                 }
                 "endcode" if extract_code.is_none() => {
                     let column = line.find("endcode").unwrap();
                     return Err(SpecError::MissingStartcode {
                         src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
                         bad_bit: (byte_offset_for_errors + column, "endcode".len()),
-                    })
-                    .into_diagnostic();
+                    }
+                    .into());
+                }
+                //~~ - between `//~ spec:startcode` and `//~ spec:endcode`,
+                //~~   a comment starting with `//~ spec:hide` hides the lines that follow,
+                //~~   up until a `//~ spec:show` statement (or the end of the code).
+                //~~   The hidden lines are replaced by a `...` comment in the language of the file
+                //~~   (e.g. `// ...` in Rust, `# ...` in Python, `(* ... *)` in OCaml),
+                //~~   with the indentation of the first hidden line.
+                "hide" | "show" if extract_code.is_none() => {
+                    let column = line.find(SPECIFICATION_INSTRUCTION).unwrap()
+                        + SPECIFICATION_INSTRUCTION.len();
+                    return Err(SpecError::HideOutsideCode {
+                        src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                        bad_bit: (byte_offset_for_errors + column, instruction.len()),
+                    }
+                    .into());
+                }
+                "hide" => {
+                    if hiding.is_some() {
+                        let column = line.find(SPECIFICATION_INSTRUCTION).unwrap()
+                            + SPECIFICATION_INSTRUCTION.len();
+                        return Err(SpecError::DoubleHide {
+                            src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                            bad_bit: (byte_offset_for_errors + column, "hide".len()),
+                        }
+                        .into());
+                    }
+                    hiding = Some(None);
+                }
+                "show" => {
+                    let Some(hidden) = hiding.take() else {
+                        let column = line.find(SPECIFICATION_INSTRUCTION).unwrap()
+                            + SPECIFICATION_INSTRUCTION.len();
+                        return Err(SpecError::MissingHide {
+                            src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                            bad_bit: (byte_offset_for_errors + column, "show".len()),
+                        }
+                        .into());
+                    };
+                    if let Some((hidden_line, marker)) = hidden {
+                        writeln!(&mut result, "{marker}").unwrap();
+                        push_line(
+                            &mut blocks,
+                            BlockKind::Code,
+                            file_name,
+                            hidden_line,
+                            &marker,
+                        );
+                    }
+                }
+                //~~ - a comment starting with `//~ spec:if(feature)` only keeps what follows,
+                //~~   up until a `//~ spec:endif` statement, if the feature is enabled
+                //~~   (see [conditionals](#conditionals)).
+                "if" => {
+                    let column = line.find(SPECIFICATION_INSTRUCTION).unwrap()
+                        + SPECIFICATION_INSTRUCTION.len();
+                    let bad_bit = (byte_offset_for_errors + column, "if".len());
+                    let condition =
+                        features::condition(argument).ok_or_else(|| SpecError::BadCondition {
+                            src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                            bad_bit,
+                        })?;
+
+                    if let Some(help) = features.unknown(condition) {
+                        let feature = condition.trim_start_matches('!').trim();
+                        let offset = column + line[column..].find(feature).unwrap();
+                        warnings.push(
+                            SpecError::UnknownFeature {
+                                feature: feature.to_string(),
+                                help,
+                                src: NamedSource::new(
+                                    file_name.to_string_lossy(),
+                                    source.to_string(),
+                                ),
+                                bad_bit: (byte_offset_for_errors + offset, feature.len()),
+                            }
+                            .into(),
+                        );
+                    }
+                    conditions.push((bad_bit.0, features.is_met(condition), extract_code));
+                    skipped_code = extract_code;
+                }
+                //~~   A condition can contain code extracted with `spec:startcode`, or be part of it,
+                //~~   but can't start on one side of a `spec:startcode` or `spec:endcode` and end on the other
+                //~~   (whether the feature is enabled or not).
+                "endif" => {
+                    let column = line.find(SPECIFICATION_INSTRUCTION).unwrap()
+                        + SPECIFICATION_INSTRUCTION.len();
+                    let bad_bit = (byte_offset_for_errors + column, "endif".len());
+                    let Some((start, _, code)) = conditions.pop() else {
+                        return Err(SpecError::MissingIf {
+                            src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                            bad_bit,
+                        }
+                        .into());
+                    };
+                    if code != extract_code {
+                        return Err(SpecError::IfAcrossCode {
+                            src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                            start: (start, "if".len()),
+                            end: bad_bit,
+                        }
+                        .into());
+                    }
+                }
+                //~~ - a comment starting with `//~ spec:item` will print the Rust item
+                //~~   (struct, enum, trait, impl block, etc.) that follows it.
+                //~~   Only blank lines, comments, and attributes can come between the two.
+                //~~   Functions are printed without their body.
+                "item" if extract_code.is_none() => {
+                    let column = line.find("item").unwrap();
+                    let bad_bit = (byte_offset_for_errors + column, "item".len());
+                    let src = || NamedSource::new(file_name.to_string_lossy(), source.to_string());
+
+                    if file_name.extension().and_then(|ext| ext.to_str()) != Some("rs") {
+                        return Err(SpecError::ItemOutsideRust {
+                            src: src(),
+                            bad_bit,
+                        }
+                        .into());
+                    }
+
+                    if rust_items.is_none() {
+                        rust_items = Some(RustItems::parse(file_name, &source)?);
+                    }
+
+                    let item = rust_items
+                        .as_ref()
+                        .and_then(|items| items.after(&source, line_number + 1))
+                        .ok_or_else(|| SpecError::MissingItem {
+                            src: src(),
+                            bad_bit,
+                        })?;
+
+                    let code = item.extract(&source, true);
+                    writeln!(&mut result, "```{lang}").unwrap();
+                    writeln!(&mut result, "{code}").unwrap();
+                    writeln!(&mut result, "```").unwrap();
+
+                    let (first_line, last_line) = item.lines();
+                    blocks.push(Block {
+                        kind: BlockKind::Code,
+                        file: file_name.to_path_buf(),
+                        first_line,
+                        last_line,
+                        content: format!("{code}\n"),
+                    });
+                    write_permalink(&mut result, &repository, file_name, first_line, last_line);
+                }
+                //~~   It can't be used between `//~ spec:startcode` and `//~ spec:endcode`.
+                "item" => {
+                    let column = line.find("item").unwrap();
+                    return Err(SpecError::ItemInCode {
+                        src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                        bad_bit: (byte_offset_for_errors + column, "item".len()),
+                    }
+                    .into());
+                }
+                //~~ - a comment starting with `//~ spec:begin(name)` starts a named fragment,
+                //~~   which ends with a `//~ spec:end(name)` statement.
+                //~~   Everything extracted in between is also available on its own
+                //~~   (in addition to being part of the content of the file).
+                //~~   A fragment can contain code extracted with `spec:startcode`,
+                //~~   or be part of it, but can't start on one side of a `spec:startcode` or `spec:endcode`
+                //~~   and end on the other.
+                "begin" => {
+                    let column = line.find(SPECIFICATION_INSTRUCTION).unwrap()
+                        + SPECIFICATION_INSTRUCTION.len();
+                    let bad_bit = (byte_offset_for_errors + column, "begin".len());
+                    let name =
+                        fragment_name(argument).ok_or_else(|| SpecError::BadFragmentName {
+                            src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                            bad_bit,
+                        })?;
+
+                    if open_fragments.iter().any(|(open, _, _, _)| *open == name) {
+                        return Err(SpecError::DoubleBegin {
+                            src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                            bad_bit,
+                        }
+                        .into());
+                    }
+
+                    open_fragments.push((name, bad_bit.0, result.len(), extract_code));
+                }
+                "end" => {
+                    let column = line.find(SPECIFICATION_INSTRUCTION).unwrap()
+                        + SPECIFICATION_INSTRUCTION.len();
+                    let bad_bit = (byte_offset_for_errors + column, "end".len());
+                    let name =
+                        fragment_name(argument).ok_or_else(|| SpecError::BadFragmentName {
+                            src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                            bad_bit,
+                        })?;
+
+                    let idx = open_fragments
+                        .iter()
+                        .position(|(open, _, _, _)| *open == name)
+                        .ok_or_else(|| SpecError::MissingBegin {
+                            src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                            bad_bit,
+                        })?;
+                    let (_, begin, start, code_block) = open_fragments.remove(idx);
+                    if code_block != extract_code {
+                        return Err(SpecError::FragmentAcrossCode {
+                            src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                            begin: (begin, "begin".len()),
+                            end: bad_bit,
+                        }
+                        .into());
+                    }
+
+                    fragments
+                        .entry(name.to_string())
+                        .or_default()
+                        .push_str(&result[start..]);
+                }
+                //~~ - a comment starting with `//~ spec:anchor(name)` creates an anchor,
+                //~~   that can be referred to from anywhere in the specification with `{ref:name}`
+                //~~   (see [references](#references)).
+                "anchor" => {
+                    let column = line.find(SPECIFICATION_INSTRUCTION).unwrap()
+                        + SPECIFICATION_INSTRUCTION.len();
+                    let name = anchor_name(argument).ok_or_else(|| SpecError::BadAnchorName {
+                        src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                        bad_bit: (byte_offset_for_errors + column, "anchor".len()),
+                    })?;
+
+                    writeln!(&mut result, "<a id=\"{name}\"></a>\n").unwrap();
+                }
+                //~~ - a comment starting with `//~ spec:req(ID)` registers a normative requirement,
+                //~~   whose text follows the instruction (see [requirements](#requirements)).
+                "req" => {
+                    let column = line.find(SPECIFICATION_INSTRUCTION).unwrap()
+                        + SPECIFICATION_INSTRUCTION.len();
+                    let id = requirements::requirement_id(argument).ok_or_else(|| {
+                        SpecError::BadRequirementId {
+                            src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                            bad_bit: (byte_offset_for_errors + column, "req".len()),
+                        }
+                    })?;
+                    let text = comment.split_once(')').map_or("", |(_, text)| text.trim());
+
+                    requirements.push(Requirement {
+                        id: id.to_string(),
+                        text: text.to_string(),
+                        file: file_name.to_path_buf(),
+                        line: line_number + 1,
+                        offset: byte_offset_for_errors + column + line[column..].find(id).unwrap(),
+                        tests: vec![],
+                    });
+                    in_requirement = true;
+
+                    // a requirement starts its own paragraph
+                    if !result.is_empty() && !result.ends_with("\n\n") {
+                        result.push('\n');
+                    }
+                    writeln!(&mut result, "<a id=\"{id}\"></a>**{id}**: {text}").unwrap();
+                }
+                //~~ - a comment starting with `//~ spec:tests(ID)` links the test function that follows
+                //~~   to the requirements it covers (see [traceability](#traceability)).
+                "tests" => {
+                    let column = line.find(SPECIFICATION_INSTRUCTION).unwrap()
+                        + SPECIFICATION_INSTRUCTION.len();
+                    let bad_bit = (byte_offset_for_errors + column, "tests".len());
+                    let src = || NamedSource::new(file_name.to_string_lossy(), source.to_string());
+
+                    let ids: Vec<_> = argument
+                        .map(|ids| ids.split(',').map(str::trim).collect())
+                        .unwrap_or_default();
+                    if ids.is_empty()
+                        || ids
+                            .iter()
+                            .any(|id| requirements::requirement_id(Some(id)).is_none())
+                    {
+                        return Err(SpecError::BadRequirementId {
+                            src: src(),
+                            bad_bit,
+                        }
+                        .into());
+                    }
+
+                    let name = traceability::test_name(
+                        source.lines().skip(line_number + 1),
+                        language.line_comment.as_deref(),
+                    )
+                    .ok_or_else(|| SpecError::MissingTest {
+                        src: src(),
+                        bad_bit,
+                    })?;
+
+                    tests.push(TestLink {
+                        test: Test {
+                            name,
+                            file: file_name.to_path_buf(),
+                            line: line_number + 1,
+                        },
+                        requirements: ids.into_iter().map(str::to_string).collect(),
+                        offset: bad_bit.0,
+                    });
                 }
                 //~~ - error on any other instructions
                 _ => {
-                    let column = line.find("spec:").unwrap();
-                    let instruction = line.split_once("spec:").unwrap().1;
-                    Err(SpecError::BadInstruction {
+                    let column = line.find(SPECIFICATION_INSTRUCTION).unwrap();
+                    let help = match did_you_mean(instruction, &INSTRUCTIONS) {
+                        Some(candidate) => format!("did you mean `spec:{candidate}`?"),
+                        None => format!(
+                            "the instructions are {}",
+                            INSTRUCTIONS
+                                .iter()
+                                .map(|instruction| format!("`spec:{instruction}`"))
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                    };
+                    return Err(SpecError::BadInstruction {
+                        instruction: instruction.to_string(),
+                        help,
                         src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
-                        bad_bit: (byte_offset_for_errors + column, 0),
-                    })
-                    .wrap_err_with(|| format!("the instruction you gave: {instruction}"))?;
+                        bad_bit: (
+                            byte_offset_for_errors + column,
+                            SPECIFICATION_INSTRUCTION.len() + instruction.len(),
+                        ),
+                    }
+                    .into());
                 }
             };
         } else {
             //~ 5. if we are not seeing an instruction, figure out if:
+            let end_comment = match (in_spec_comment, delimiter) {
+                (Some((_, end)), _) => Some(end),
+                (None, Some((_, end))) => *end,
+                (None, None) => None,
+            };
             let comment = if let Some(end) = end_comment {
                 if has_end(end, comment) {
                     //~~ - the comment is ending
 
                     in_spec_comment = None;
-                    comment.trim_end_matches(end)
+                    comment.trim_end().trim_end_matches(end)
                 } else {
                     //~~ - or goes on to the next line
 
-                    if in_spec_comment.is_none() {
-                        let offset = line.find(start_comment).unwrap() + start_comment.len();
-                        in_spec_comment = Some(offset);
+                    if let Some((start_comment, _)) = delimiter {
+                        let offset =
+                            line.find(start_comment.as_str()).unwrap() + start_comment.len();
+                        in_spec_comment = Some((offset, end));
                     }
 
                     comment
@@ -184,7 +945,39 @@ pub fn parse_code(
             let comment = no_more_tilde.strip_prefix(' ').unwrap_or(comment);
             indented.push_str(comment);
 
+            // the text of a requirement goes on up to an empty line,
+            // and RFC 2119 keywords shouldn't be used outside of requirements
+            if comment.trim_start().starts_with("```") {
+                in_code_block = !in_code_block;
+            }
+            if in_requirement && !comment.trim().is_empty() {
+                let requirement = requirements.last_mut().unwrap();
+                if !requirement.text.is_empty() {
+                    requirement.text.push(' ');
+                }
+                requirement.text.push_str(comment.trim());
+            } else if !in_code_block {
+                in_requirement = false;
+                for (column, keyword) in requirements::keywords(line) {
+                    warnings.push(
+                        SpecError::KeywordOutsideRequirement {
+                            keyword: keyword.to_string(),
+                            src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+                            bad_bit: (byte_offset_for_errors + column, keyword.len()),
+                        }
+                        .into(),
+                    );
+                }
+            }
+
             writeln!(&mut result, "{indented}").unwrap();
+            push_line(
+                &mut blocks,
+                BlockKind::Prose,
+                file_name,
+                line_number + 1,
+                &indented,
+            );
         }
 
         byte_offset_for_errors += line.len() + 1; // +1 for the newline character
@@ -195,11 +988,36 @@ pub fn parse_code(
     if let Some(offset) = extract_code {
         return Err(SpecError::MissingEndcode {
             src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
-            bad_bit: (offset, 0),
-        })
-        .into_diagnostic();
+            bad_bit: (offset, "startcode".len()),
+        }
+        .into());
+    }
+
+    //~ 8. as well as every named fragment
+    if let Some((_, offset, _, _)) = open_fragments.first() {
+        return Err(SpecError::MissingEnd {
+            src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+            bad_bit: (*offset, "begin".len()),
+        }
+        .into());
+    }
+
+    //~ 9. as well as every condition
+    if let Some((offset, _, _)) = conditions.first() {
+        return Err(SpecError::MissingEndif {
+            src: NamedSource::new(file_name.to_string_lossy(), source.to_string()),
+            bad_bit: (*offset, "if".len()),
+        }
+        .into());
     }
 
-    //~ 8. return the result
-    Ok(result)
+    //~ 10. return the result
+    Ok(ParsedFile {
+        content: result,
+        fragments,
+        blocks,
+        requirements,
+        tests,
+        warnings,
+    })
 }
@@ -17,6 +17,98 @@ pub enum SpecError {
     #[diagnostic(help("cargo-specification can only parse files that have an extension"))]
     CantParseFile(PathBuf),
 
+    #[error("Error parsing file")]
+    #[diagnostic(help(
+        "spec:hide and spec:show can only be used between spec:startcode and spec:endcode"
+    ))]
+    HideOutsideCode {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("this instruction is not in extracted code")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help("the lines are already hidden"))]
+    DoubleHide {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("this hide instruction is invalid")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help("missing a hide instruction before the show"))]
+    MissingHide {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("this show instruction is invalid")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help(
+        "conditions are a feature name (letters, digits, dashes, and underscores), optionally negated with `!`"
+    ))]
+    BadCondition {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("try spec:if(feature) or spec:if(!feature)")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help("missing an endif instruction"))]
+    MissingEndif {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("this if instruction is not terminated")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help(
+        "a condition can't start outside of a spec:startcode and end inside of it (or the opposite)"
+    ))]
+    IfAcrossCode {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("the condition starts here")]
+        start: (usize, usize),
+
+        #[label("and ends here")]
+        end: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help("missing an if instruction before the endif"))]
+    MissingIf {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("this endif instruction is invalid")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Feature `{feature}` is not listed in any profile")]
+    #[diagnostic(severity(Warning), help("{help}"))]
+    UnknownFeature {
+        feature: String,
+        help: String,
+
+        #[source_code]
+        src: NamedSource,
+
+        #[label("this feature")]
+        bad_bit: (usize, usize),
+    },
+
     #[error("Error parsing file")]
     #[diagnostic(help("missing a startcode instruction before the endcode"))]
     MissingStartcode {
@@ -47,16 +139,369 @@ pub enum SpecError {
         bad_bit: (usize, usize),
     },
 
-    #[error("Error parsing file")]
-    #[diagnostic(help("unrecognized instruction"))]
+    #[error("Unknown instruction `spec:{instruction}`")]
+    #[diagnostic(help("{help}"))]
     BadInstruction {
+        instruction: String,
+        help: String,
+
         #[source_code]
         src: NamedSource,
 
-        #[label("try spec:startcode or spec:endcode instead")]
+        #[label("unrecognized instruction")]
         bad_bit: (usize, usize),
     },
 
+    #[error("Invalid option `{option}` for `spec:startcode`")]
+    #[diagnostic(help(
+        "the options are `lang=name`, `linenos`, and `highlight=3-5` (lines counted from the first line of the code)"
+    ))]
+    BadCodeOption {
+        option: String,
+        #[source_code]
+        src: NamedSource,
+
+        #[label("invalid option")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help("fragment names can only contain letters, digits, and underscores"))]
+    BadFragmentName {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("try spec:begin(name) or spec:end(name)")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help("anchor names can only contain letters, digits, dashes, and underscores"))]
+    BadAnchorName {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("try spec:anchor(name)")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help(
+        "requirement IDs can only contain letters, digits, dashes, and underscores"
+    ))]
+    BadRequirementId {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("invalid requirement ID")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help("spec:tests must be followed by a test function"))]
+    MissingTest {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("no test function after this")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Test `{test}` covers requirement `{id}`, which is not registered")]
+    #[diagnostic(help("{help}"))]
+    UnknownRequirement {
+        test: String,
+        id: String,
+        help: String,
+
+        #[source_code]
+        src: NamedSource,
+
+        #[label("this instruction")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Profile `{0}` not found in the specification file")]
+    #[diagnostic(help("{1}"))]
+    UnknownProfile(String, String),
+
+    #[error("Some requirements are not covered by any test: {0}")]
+    #[diagnostic(help("link tests to requirements with `spec:tests(ID)`"))]
+    UncoveredRequirements(String),
+
+    #[error("Requirement `{id}` is registered more than once")]
+    #[diagnostic(help("it was first registered at {first}"))]
+    DuplicateRequirement {
+        id: String,
+        first: String,
+
+        #[source_code]
+        src: NamedSource,
+
+        #[label("registered again here")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("`{keyword}` is used outside of a requirement")]
+    #[diagnostic(
+        severity(Warning),
+        help("register the requirement with `spec:req(ID)` so that it can be tracked")
+    )]
+    KeywordOutsideRequirement {
+        keyword: String,
+
+        #[source_code]
+        src: NamedSource,
+
+        #[label("this keyword")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help("we are already in a fragment with this name"))]
+    DoubleBegin {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("this begin instruction is invalid")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help(
+        "a fragment can't start outside of a spec:startcode and end inside of it (or the opposite)"
+    ))]
+    FragmentAcrossCode {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("the fragment starts here")]
+        begin: (usize, usize),
+
+        #[label("and ends here")]
+        end: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help("missing a begin instruction with the same name before the end"))]
+    MissingBegin {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("This bit here")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help("missing end instruction"))]
+    MissingEnd {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("this fragment is not terminated")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help("spec:item can only be used in Rust files"))]
+    ItemOutsideRust {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("this item instruction is invalid")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help(
+        "spec:item cannot be used inside spec:startcode, end the code with spec:endcode first"
+    ))]
+    ItemInCode {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("this item instruction is inside extracted code")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing file")]
+    #[diagnostic(help("spec:item must be directly followed by a Rust item"))]
+    MissingItem {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("there is no item right after this instruction")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error parsing Rust file: {message}")]
+    #[diagnostic(help("spec:item needs to parse the file"))]
+    CantParseRust {
+        message: String,
+
+        #[source_code]
+        src: NamedSource,
+
+        #[label("this does not parse")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Could not find the Rust item `{0}`: {1}")]
+    #[diagnostic(help(
+        "items are referred to by their path in the crate, e.g. `item:crate::module::Item`"
+    ))]
+    ItemNotFound(String, String),
+
+    #[error("The item `{0}` is not defined in the files of section {1}")]
+    #[diagnostic(help("only the doc comments of the files listed by the section are extracted"))]
+    ItemOutsideSection(String, String),
+
+    #[error("The pattern `{0}` of section {1} does not match any file")]
+    #[diagnostic(help("make sure the path is relative to the specification file"))]
+    NoMatchingFile(String, String),
+
+    #[error("The pattern `{0}` of section {1} is invalid")]
+    #[diagnostic(help("{2}"))]
+    BadPattern(String, String, String),
+
+    #[error("Could not run typst")]
+    #[diagnostic(help(
+        "typst is needed to produce PDFs, see https://github.com/typst/typst for how to install it (or use the typst output format)"
+    ))]
+    MissingTypesetter,
+
+    #[error("Typst could not produce the PDF: {0}")]
+    #[diagnostic(help("you can use the typst output format to inspect the document"))]
+    TypesettingFailed(String),
+
     #[error("This is not a git repository, you can't use `@/` in the path of section {0}")]
     NotGitRepo(String),
+
+    #[error("Several outputs are written at {0}")]
+    #[diagnostic(help("give each output of the specification file its own `path`"))]
+    DuplicateOutput(PathBuf),
+
+    #[error("The specification is out of date: {0}")]
+    #[diagnostic(help("run `cargo spec build` to update it"))]
+    OutdatedSpecification(String),
+
+    #[error("Unknown placeholder `{placeholder}` in the template")]
+    #[diagnostic(help("{help}"))]
+    UnknownPlaceholder {
+        placeholder: String,
+        help: String,
+
+        #[source_code]
+        src: NamedSource,
+
+        #[label("this placeholder doesn't refer to anything")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("The template `{path}` has {} problems", problems.len())]
+    BadTemplate {
+        path: PathBuf,
+
+        #[related]
+        problems: Vec<SpecError>,
+    },
+
+    #[error("Placeholder `{{{placeholder}}}` uses the syntax of previous versions of cargo-spec")]
+    #[diagnostic(help("placeholders are now written `{{{{ {placeholder} }}}}`"))]
+    LegacyPlaceholder {
+        placeholder: String,
+
+        #[source_code]
+        src: NamedSource,
+
+        #[label("this would be printed as is")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Error in template")]
+    #[diagnostic(help(
+        "see https://docs.rs/minijinja/latest/minijinja/syntax/ for the syntax of templates"
+    ))]
+    TemplateError {
+        #[source_code]
+        src: NamedSource,
+
+        #[label("{message}")]
+        bad_bit: (usize, usize),
+
+        message: String,
+    },
+
+    #[error("Template `{0}` not found")]
+    #[diagnostic(help("the path of the template is relative to the specification file"))]
+    MissingTemplate(PathBuf),
+
+    #[error("The template can't be rendered: {0}")]
+    CantRenderTemplate(String),
+
+    #[error("Invalid specification file: {message}")]
+    BadManifest {
+        message: String,
+
+        #[source_code]
+        src: NamedSource,
+
+        #[label("here")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Reference to `{id}` has no target")]
+    #[diagnostic(help("{help}"))]
+    MissingReference {
+        id: String,
+        help: String,
+
+        #[source_code]
+        src: NamedSource,
+
+        #[label("this reference")]
+        bad_bit: (usize, usize),
+    },
+
+    #[error("Reference to `{0}` has no target")]
+    #[diagnostic(help("{1}"))]
+    MissingReferenceTarget(String, String),
+
+    #[error("{} references have no target", problems.len())]
+    MissingReferences {
+        #[related]
+        problems: Vec<SpecError>,
+    },
+
+    #[error("Section `{section}` is never used in the template")]
+    #[diagnostic(
+        severity(Warning),
+        help("use it with `{{{{ sections.{section} }}}}` in the template, or remove it from the specification file")
+    )]
+    UnusedSection {
+        section: String,
+
+        // points to the specification file, if the specification was read from one
+        // (it's attached to the report, as there's no source otherwise)
+        #[label("never used")]
+        bad_bit: (usize, usize),
+    },
+}
+
+/// An entry of the specification file that caused an error
+/// (this is not a variant of [SpecError], as the related error can be of any type)
+#[derive(Error, Diagnostic, Debug)]
+#[error("Couldn't use `{entry}`, listed in `{key}`")]
+pub struct BadEntry {
+    pub key: String,
+    pub entry: String,
+
+    #[source_code]
+    pub src: NamedSource,
+
+    #[label("listed here")]
+    pub bad_bit: (usize, usize),
+
+    #[related]
+    pub problems: Vec<miette::Report>,
 }
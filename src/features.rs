@@ -0,0 +1,135 @@
+use miette::Result;
+use std::{
+    collections::BTreeSet,
+    path::{Path, PathBuf},
+};
+
+use crate::{errors::SpecError, template::did_you_mean, toml_parser::Specification};
+
+//~ A specification can have several editions (e.g. a public one and an internal one),
+//~ which include different content depending on the features they are built with:
+//~
+//~ 1. in spec comments, the content between `spec:if(feature)` and `spec:endif`
+//~    (spec comments, extracted code, and instructions) is only included if the feature is enabled,
+//~    and the content between `spec:if(!feature)` and `spec:endif` only if it isn't.
+//~    Conditions can be nested.
+//~ 2. in the template, the enabled features are available as `features` (e.g. `{% if "internal" in features %}`),
+//~    and the name of the profile as `profile`
+//~ 3. features are enabled with `--features` (e.g. `--features internal,drafts`),
+//~    or with `--profile`, which enables the features listed for the profile
+//~    in the `[profiles]` table of the specification file (e.g. `internal = ["internal", "drafts"]`)
+//~ 4. `--profile` can be given several times to build several editions at once,
+//~    in which case the name of the profile is added to the name of each output file
+//~    (e.g. `specification-internal.md`)
+//~ 5. if the specification file has profiles, a feature used in a condition
+//~    that isn't listed in any profile (nor enabled with `--features`) produces a warning
+//~
+
+/// An edition of the specification: a profile of the specification file (if any),
+/// along with other features to enable
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Edition {
+    pub profile: Option<String>,
+    pub features: Vec<String>,
+}
+
+impl Edition {
+    /// The edition with the features of a profile
+    pub fn profile(profile: impl Into<String>) -> Self {
+        Self {
+            profile: Some(profile.into()),
+            features: vec![],
+        }
+    }
+
+    /// The features enabled in this edition of the specification
+    pub(crate) fn features(&self, specification: &Specification) -> Result<Features> {
+        let mut enabled: BTreeSet<String> = self.features.iter().cloned().collect();
+        if let Some(profile) = &self.profile {
+            let features = specification.profiles.get(profile).ok_or_else(|| {
+                let profiles: Vec<_> = specification.profiles.keys().map(String::as_str).collect();
+                let help = match did_you_mean(profile, &profiles) {
+                    Some(candidate) => format!("did you mean `{candidate}`?"),
+                    None if profiles.is_empty() => {
+                        "profiles are listed in the `[profiles]` table of the specification file"
+                            .to_string()
+                    }
+                    None => format!("the profiles are {}", profiles.join(", ")),
+                };
+                SpecError::UnknownProfile(profile.clone(), help)
+            })?;
+            enabled.extend(features.iter().cloned());
+        }
+
+        let mut known = BTreeSet::new();
+        if !specification.profiles.is_empty() {
+            known.extend(specification.profiles.values().flatten().cloned());
+            known.extend(enabled.iter().cloned());
+        }
+
+        Ok(Features {
+            profile: self.profile.clone(),
+            enabled,
+            known,
+        })
+    }
+
+    /// Where this edition is written when several editions are built at once
+    /// (e.g. `specification-internal.md` for the `internal` profile)
+    pub fn output_path(&self, path: &Path) -> PathBuf {
+        let Some(profile) = &self.profile else {
+            return path.to_path_buf();
+        };
+        let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+        let name = match path.extension() {
+            Some(extension) => format!("{stem}-{profile}.{}", extension.to_string_lossy()),
+            None => format!("{stem}-{profile}"),
+        };
+        path.with_file_name(name)
+    }
+}
+
+/// The features a specification is built with
+#[derive(Debug, Default)]
+pub(crate) struct Features {
+    pub profile: Option<String>,
+    pub enabled: BTreeSet<String>,
+    /// the features listed in the profiles of the specification file, if there are any
+    known: BTreeSet<String>,
+}
+
+impl Features {
+    /// If a condition (e.g. `internal` or `!internal`) is met
+    pub fn is_met(&self, condition: &str) -> bool {
+        match condition.strip_prefix('!') {
+            Some(feature) => !self.enabled.contains(feature.trim()),
+            None => self.enabled.contains(condition),
+        }
+    }
+
+    /// Returns a help message if the feature of a condition isn't listed in any profile
+    pub fn unknown(&self, condition: &str) -> Option<String> {
+        let feature = condition.trim_start_matches('!').trim();
+        if self.known.is_empty() || self.known.contains(feature) {
+            return None;
+        }
+
+        let known: Vec<_> = self.known.iter().map(String::as_str).collect();
+        Some(match did_you_mean(feature, &known) {
+            Some(candidate) => format!("did you mean `{candidate}`?"),
+            None => format!("the features of the profiles are {}", known.join(", ")),
+        })
+    }
+}
+
+/// Returns the condition of a `spec:if`, if it's valid
+/// (a feature name, made of letters, digits, dashes, and underscores, optionally negated with `!`)
+pub fn condition(argument: Option<&str>) -> Option<&str> {
+    argument.filter(|condition| {
+        let feature = condition.strip_prefix('!').unwrap_or(condition).trim();
+        !feature.is_empty()
+            && feature
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    })
+}
@@ -0,0 +1,102 @@
+use askama::Template;
+use comrak::{format_html, nodes::NodeValue, parse_document, Anchorizer, Arena};
+use std::fmt::Write as FmtWrite;
+
+use crate::{formats, toml_parser::Specification};
+
+#[derive(Template)]
+#[template(path = "html.html")]
+struct Html<'a> {
+    name: &'a str,
+    description: Option<&'a str>,
+    version: Option<&'a str>,
+    authors: String,
+    toc: String,
+    content: String,
+}
+
+/// A header of the specification
+struct Header {
+    level: u32,
+    id: String,
+    title: String,
+}
+
+/// Converts the markdown specification to a standalone HTML page
+pub fn to_html(specification: &Specification, content: &str) -> String {
+    //~ - converts markdown content to pure HTML, with an anchor on each header
+    let arena = Arena::new();
+    let options = formats::comrak_options(Some("".to_string()));
+    let root = parse_document(&arena, content, &options);
+    formats::render_code_options(root);
+
+    let mut html = vec![];
+    format_html(root, &options, &mut html).unwrap();
+    let content = String::from_utf8(html).unwrap();
+
+    //~ - generates a table of contents linking to these anchors
+    let mut headers = vec![];
+    let mut anchorizer = Anchorizer::new();
+    for node in root.descendants() {
+        if let NodeValue::Heading(heading) = &node.data.borrow().value {
+            let mut title = vec![];
+            formats::collect_text(node, &mut title);
+            let title = String::from_utf8(title).unwrap();
+            headers.push(Header {
+                level: heading.level,
+                id: anchorizer.anchorize(title.clone()),
+                title,
+            });
+        }
+    }
+
+    //~ - produces a standalone HTML page (the CSS is embedded, and nothing is loaded from the network)
+    let metadata = &specification.metadata;
+    let html_page = Html {
+        name: &metadata.name,
+        description: metadata.description.as_deref(),
+        version: metadata.version.as_deref(),
+        authors: metadata.authors.join(", "),
+        toc: table_of_contents(&headers),
+        content,
+    };
+
+    html_page.render().unwrap()
+}
+
+/// Creates nested lists of links to the headers
+fn table_of_contents(headers: &[Header]) -> String {
+    let mut toc = String::new();
+
+    // the levels of the lists that are currently open
+    let mut levels: Vec<u32> = vec![];
+    for Header { level, id, title } in headers {
+        while levels.last().is_some_and(|last| last > level) {
+            toc.push_str("</li></ul>");
+            levels.pop();
+        }
+
+        if levels.last() == Some(level) {
+            toc.push_str("</li>");
+        } else {
+            toc.push_str("<ul>");
+            levels.push(*level);
+        }
+
+        write!(toc, "<li><a href=\"#{id}\">{}</a>", escape(title)).unwrap();
+    }
+
+    for _ in levels {
+        toc.push_str("</li></ul>");
+    }
+
+    toc
+}
+
+/// Escapes text to be included in HTML
+pub(super) fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
@@ -0,0 +1,59 @@
+use indexmap::IndexMap;
+use serde::Serialize;
+use std::collections::{BTreeMap, HashMap};
+
+use crate::{comment_parser::Block, requirements::Requirement, toml_parser::Metadata};
+
+#[derive(Serialize)]
+struct Json<'a> {
+    metadata: &'a Metadata,
+    sections: Vec<Section<'a>>,
+    requirements: &'a [Requirement],
+}
+
+#[derive(Serialize)]
+struct Section<'a> {
+    name: &'a str,
+    /// everything extracted for the section, in markdown
+    content: &'a str,
+    /// the named fragments of the section (sorted by name)
+    fragments: BTreeMap<&'a str, &'a str>,
+    /// what was extracted for the section, with where it comes from
+    blocks: &'a [Block],
+}
+
+/// Converts what was extracted for the specification to JSON
+pub fn to_json(
+    metadata: &Metadata,
+    sections: &IndexMap<String, String>,
+    fragments: &IndexMap<String, HashMap<String, String>>,
+    blocks: &IndexMap<String, Vec<Block>>,
+    requirements: &[Requirement],
+) -> String {
+    //~ - for each section (in order), its content, its named fragments,
+    //~   and the blocks it was extracted from: prose, code, or instruction,
+    //~   with the file (relative to the specification file) and the lines they come from
+    let sections = sections
+        .iter()
+        .map(|(name, content)| Section {
+            name,
+            content,
+            fragments: fragments
+                .get(name)
+                .into_iter()
+                .flatten()
+                .map(|(name, content)| (name.as_str(), content.as_str()))
+                .collect(),
+            blocks: blocks.get(name).map(Vec::as_slice).unwrap_or_default(),
+        })
+        .collect();
+
+    //~ - along with the metadata of the specification, and its [requirements](#requirements)
+    let json = Json {
+        metadata,
+        sections,
+        requirements,
+    };
+
+    serde_json::to_string_pretty(&json).expect("couldn't serialize the specification") + "\n"
+}
@@ -0,0 +1,172 @@
+use serde::Serialize;
+use std::{collections::HashMap, fmt::Write as FmtWrite, path::Path};
+
+use crate::{
+    formats::{self, OutputFile},
+    toml_parser::Specification,
+};
+
+/// The `book.toml` file of an mdBook
+#[derive(Serialize)]
+struct BookToml<'a> {
+    book: Book<'a>,
+}
+
+#[derive(Serialize)]
+struct Book<'a> {
+    title: &'a str,
+    authors: &'a [String],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    description: Option<&'a str>,
+    src: &'a str,
+}
+
+/// A chapter of the book
+struct Chapter {
+    title: String,
+    file: String,
+    /// the lines of the specification that make the chapter
+    lines: std::ops::Range<usize>,
+}
+
+/// Splits the specification into the files of an [mdBook](https://rust-lang.github.io/mdBook/)
+/// living in the `output_dir` directory
+pub fn to_mdbook(
+    specification: &Specification,
+    content: &str,
+    output_dir: &Path,
+) -> Vec<OutputFile> {
+    let src_dir = output_dir.join("src");
+
+    let mut files = vec![];
+
+    //~ - the `book.toml` file is filled with the metadata of the specification
+    let metadata = &specification.metadata;
+    let book_toml = BookToml {
+        book: Book {
+            title: &metadata.name,
+            authors: &metadata.authors,
+            description: metadata.description.as_deref(),
+            src: "src",
+        },
+    };
+    files.push(OutputFile::new(
+        output_dir.join("book.toml"),
+        toml::to_string(&book_toml).expect("couldn't serialize book.toml"),
+    ));
+
+    //~ - the specification is split into chapters on its highest headers
+    //~   (if there's only one of them, it's the title of the specification and the headers below are used).
+    //~   Anything before the first chapter becomes the introduction.
+    let lines: Vec<_> = content.lines().collect();
+    let headers = formats::headers(content);
+
+    let top_level: Vec<_> = headers.iter().filter(|h| h.top_level).collect();
+    let highest = top_level.iter().map(|h| h.level).min();
+    let title = top_level
+        .first()
+        .filter(|first| {
+            Some(first.level) == highest
+                && top_level
+                    .iter()
+                    .filter(|h| Some(h.level) == highest)
+                    .count()
+                    == 1
+        })
+        .copied();
+    let split_level = top_level
+        .iter()
+        .filter(|h| Some(h.line) != title.map(|t| t.line))
+        .map(|h| h.level)
+        .min();
+
+    //~   Each chapter is written to a file named after the id of its header
+    //~   (or `section-N.md`, for the N-th chapter, if its header has no id, e.g. if it's made of symbols only).
+    //~   Chapters whose id is `summary` or `readme` get a `-chapter` suffix,
+    //~   so they don't replace `SUMMARY.md` or `README.md` on case-insensitive file systems.
+    //~   Chapters that are no longer generated are not removed from the `src` directory,
+    //~   but they don't appear in `SUMMARY.md` anymore, so they're not part of the book.
+    let mut chapters: Vec<Chapter> = vec![];
+    for header in top_level.iter().filter(|h| Some(h.level) == split_level) {
+        if let Some(previous) = chapters.last_mut() {
+            previous.lines.end = header.line;
+        }
+        let file = if header.id.is_empty() {
+            format!("section-{}.md", chapters.len() + 1)
+        } else if ["summary", "readme"].contains(&header.id.to_lowercase().as_str()) {
+            format!("{}-chapter.md", header.id)
+        } else {
+            format!("{}.md", header.id)
+        };
+        chapters.push(Chapter {
+            title: header.title.clone(),
+            file,
+            lines: header.line..lines.len(),
+        });
+    }
+
+    let intro_start = match title {
+        Some(title) if title.setext => title.line + 2,
+        Some(title) => title.line + 1,
+        None => 0,
+    };
+    let intro_end = chapters.first().map_or(lines.len(), |c| c.lines.start);
+    let intro = Chapter {
+        title: "Introduction".to_string(),
+        file: "README.md".to_string(),
+        lines: intro_start.min(intro_end)..intro_end,
+    };
+    let has_intro = lines[intro.lines.clone()]
+        .iter()
+        .any(|l| !l.trim().is_empty());
+
+    //~ - links to headers are updated to point to the chapter that contains them
+    let chapter_of = |line: usize| {
+        chapters
+            .iter()
+            .find(|c| c.lines.contains(&line))
+            .unwrap_or(&intro)
+    };
+    let anchors = formats::anchors(content);
+    let header_files: HashMap<_, _> = headers
+        .iter()
+        .map(|h| (h.line, h.id.as_str()))
+        .chain(anchors.iter().map(|(line, id)| (*line, id.as_str())))
+        .map(|(line, id)| (id, chapter_of(line).file.as_str()))
+        .collect();
+
+    let mut summary = "# Summary\n\n".to_string();
+    if has_intro {
+        writeln!(summary, "[{}](README.md)\n", intro.title).unwrap();
+    }
+    for chapter in std::iter::once(&intro)
+        .filter(|_| has_intro)
+        .chain(&chapters)
+    {
+        if chapter.file != intro.file {
+            writeln!(summary, "- [{}]({})", escape(&chapter.title), chapter.file).unwrap();
+        }
+
+        let mut chapter_content = lines[chapter.lines.clone()].join("\n");
+        chapter_content.push('\n');
+        for (id, file) in &header_files {
+            if *file != chapter.file {
+                chapter_content =
+                    chapter_content.replace(&format!("](#{id})"), &format!("]({file}#{id})"));
+            }
+        }
+
+        files.push(OutputFile::new(
+            src_dir.join(&chapter.file),
+            chapter_content,
+        ));
+    }
+    files.push(OutputFile::new(src_dir.join("SUMMARY.md"), summary));
+
+    files
+}
+
+/// Escapes the title of a chapter in `SUMMARY.md`
+fn escape(title: &str) -> String {
+    title.replace('[', "\\[").replace(']', "\\]")
+}
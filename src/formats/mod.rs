@@ -1,2 +1,258 @@
-pub mod markdown;
+use comrak::{
+    nodes::{AstNode, NodeHtmlBlock, NodeValue},
+    parse_document, Anchorizer, Arena, ComrakExtensionOptions, ComrakOptions, ComrakParseOptions,
+    ComrakRenderOptions,
+};
+use std::path::PathBuf;
+
+pub mod html;
+pub mod json;
+pub mod mdbook;
 pub mod respec;
+pub mod typst;
+
+/// A header of the specification
+pub struct Header {
+    /// the line of the header (starting at 0)
+    pub line: usize,
+    pub level: u32,
+    pub setext: bool,
+    pub id: String,
+    pub title: String,
+    /// if the header is not nested in another block (e.g. a list)
+    pub top_level: bool,
+}
+
+/// A file produced by an output format
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct OutputFile {
+    pub path: PathBuf,
+    pub content: Vec<u8>,
+}
+
+impl OutputFile {
+    pub fn new(path: PathBuf, content: impl Into<Vec<u8>>) -> Self {
+        Self {
+            path,
+            content: content.into(),
+        }
+    }
+
+    /// The content of the file, if it's text (which is the case for every format but PDF)
+    pub fn as_str(&self) -> Option<&str> {
+        std::str::from_utf8(&self.content).ok()
+    }
+}
+
+/// The options of a code block (e.g. `spec:startcode(lang=rust, linenos, highlight=3-5)`),
+/// which are written after the language in the info string of its fence (e.g. ```` ```rust linenos highlight=3-5 ````)
+#[derive(Debug, Default, Clone, PartialEq, Eq)]
+pub struct CodeOptions {
+    pub lang: Option<String>,
+    /// number the lines
+    pub linenos: bool,
+    /// the ranges of lines to highlight (starting at 1, inclusive)
+    pub highlight: Vec<(usize, usize)>,
+}
+
+impl CodeOptions {
+    /// Parses the argument of a `spec:startcode` instruction (options separated by commas).
+    /// Returns the invalid option, if there is one.
+    pub fn parse(argument: &str) -> Result<Self, String> {
+        let mut options = Self::default();
+        for option in argument.split(',').map(str::trim).filter(|o| !o.is_empty()) {
+            if !options.set(option) {
+                return Err(option.to_string());
+            }
+        }
+        Ok(options)
+    }
+
+    /// Parses the info string of a fence, ignoring what isn't an option
+    pub fn from_info(info: &str) -> Self {
+        let mut words = info.split_whitespace();
+        let mut options = Self {
+            lang: words.next().map(str::to_string),
+            ..Default::default()
+        };
+        for word in words {
+            options.set(word);
+        }
+        options
+    }
+
+    /// The info string of the fence of the code block
+    pub fn info_string(&self) -> String {
+        let mut info = self.lang.clone().unwrap_or_default();
+        if info.is_empty() && (self.linenos || !self.highlight.is_empty()) {
+            info.push_str("text");
+        }
+        if self.linenos {
+            info.push_str(" linenos");
+        }
+        for (first, last) in &self.highlight {
+            match first == last {
+                true => info.push_str(&format!(" highlight={first}")),
+                false => info.push_str(&format!(" highlight={first}-{last}")),
+            }
+        }
+        info
+    }
+
+    /// Sets an option (e.g. `highlight=3-5`), returns false if it's not valid
+    fn set(&mut self, option: &str) -> bool {
+        match option.split_once('=') {
+            None if option == "linenos" => self.linenos = true,
+            Some(("lang", lang)) if !lang.trim().is_empty() => {
+                self.lang = Some(lang.trim().to_string())
+            }
+            Some(("highlight", range)) => {
+                let (first, last) = range.split_once('-').unwrap_or((range, range));
+                match (first.trim().parse(), last.trim().parse()) {
+                    (Ok(first), Ok(last)) if 0 < first && first <= last => {
+                        self.highlight.push((first, last))
+                    }
+                    _ => return false,
+                }
+            }
+            _ => return false,
+        }
+        true
+    }
+
+    /// If a line (starting at 1) is highlighted
+    pub fn highlighted(&self, line: usize) -> bool {
+        self.highlight
+            .iter()
+            .any(|(first, last)| (*first..=*last).contains(&line))
+    }
+}
+
+/// Renders the code blocks that have options as HTML,
+/// with a `linenos` class on the `pre` and each line in a `span` with its number (and a `highlighted` class if needed)
+pub fn render_code_options<'a>(root: &'a AstNode<'a>) {
+    for node in root.descendants() {
+        let mut ast = node.data.borrow_mut();
+        let NodeValue::CodeBlock(code_block) = &ast.value else {
+            continue;
+        };
+        let options = CodeOptions::from_info(&String::from_utf8_lossy(&code_block.info));
+        if !options.linenos && options.highlight.is_empty() {
+            continue;
+        }
+
+        let code = String::from_utf8_lossy(&code_block.literal);
+        let mut html = format!(
+            "<pre lang=\"{}\"{}><code>",
+            html::escape(options.lang.as_deref().unwrap_or_default()),
+            if options.linenos {
+                " class=\"linenos\""
+            } else {
+                ""
+            },
+        );
+        for (idx, line) in code.lines().enumerate() {
+            let class = match options.highlighted(idx + 1) {
+                true => "line highlighted",
+                false => "line",
+            };
+            html.push_str(&format!(
+                "<span class=\"{class}\" data-line=\"{}\">{}</span>\n",
+                idx + 1,
+                html::escape(line)
+            ));
+        }
+        html.push_str("</code></pre>\n");
+
+        ast.value = NodeValue::HtmlBlock(NodeHtmlBlock {
+            block_type: 0,
+            literal: html.into_bytes(),
+        });
+    }
+}
+
+/// The options used to convert the markdown specification to HTML.
+/// If `header_ids` is set, headers get an anchor (prefixed with `header_ids`).
+pub fn comrak_options(header_ids: Option<String>) -> ComrakOptions {
+    ComrakOptions {
+        extension: ComrakExtensionOptions {
+            strikethrough: true,
+            tagfilter: true,
+            table: true,
+            autolink: true,
+            tasklist: true,
+            superscript: true,
+            header_ids,
+            footnotes: true,
+            description_lists: true,
+            front_matter_delimiter: None,
+        },
+        parse: ComrakParseOptions {
+            smart: true,
+            default_info_string: None,
+        },
+        render: ComrakRenderOptions {
+            hardbreaks: false,
+            github_pre_lang: true,
+            width: 0,
+            unsafe_: true, // it's our spec afterall
+            escape: false,
+        },
+    }
+}
+
+/// Collects the text of a node (e.g. a header), the same way comrak does to create anchors
+pub fn collect_text<'a>(node: &'a AstNode<'a>, output: &mut Vec<u8>) {
+    match &node.data.borrow().value {
+        NodeValue::Text(literal) => output.extend_from_slice(literal),
+        NodeValue::Code(code) => output.extend_from_slice(&code.literal),
+        NodeValue::LineBreak | NodeValue::SoftBreak => output.push(b' '),
+        _ => {
+            for child in node.children() {
+                collect_text(child, output);
+            }
+        }
+    }
+}
+
+/// Returns all the headers of the specification, along with their anchors
+pub fn headers(content: &str) -> Vec<Header> {
+    let arena = Arena::new();
+    let root = parse_document(&arena, content, &comrak_options(None));
+
+    let mut headers = vec![];
+    let mut anchorizer = Anchorizer::new();
+    for node in root.descendants() {
+        let ast = node.data.borrow();
+        if let NodeValue::Heading(heading) = &ast.value {
+            let mut title = vec![];
+            collect_text(node, &mut title);
+            let title = String::from_utf8_lossy(&title).to_string();
+            headers.push(Header {
+                line: ast.start_line as usize - 1,
+                level: heading.level,
+                setext: heading.setext,
+                id: anchorizer.anchorize(title.clone()),
+                title,
+                top_level: node.parent().is_some_and(|parent| parent.same_node(root)),
+            });
+        }
+    }
+
+    headers
+}
+
+/// Returns the anchors explicitly written in the specification (as `<a id="anchor"></a>`),
+/// along with their lines (starting at 0)
+pub fn anchors(content: &str) -> Vec<(usize, String)> {
+    let mut anchors = vec![];
+    for (line_number, line) in content.lines().enumerate() {
+        for (offset, _) in line.match_indices("<a id=\"") {
+            let id = &line[offset + "<a id=\"".len()..];
+            if let Some(end) = id.find('"') {
+                anchors.push((line_number, id[..end].to_string()));
+            }
+        }
+    }
+    anchors
+}
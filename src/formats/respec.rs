@@ -1,11 +1,6 @@
+use crate::{formats, toml_parser::Specification};
 use askama::Template;
-use comrak::{
-    markdown_to_html, ComrakExtensionOptions, ComrakOptions, ComrakParseOptions,
-    ComrakRenderOptions,
-};
-use std::{fs::File, io::Write as IOWrite, path::PathBuf};
-
-use crate::toml_parser::Specification;
+use comrak::{format_html, parse_document, Arena};
 
 #[derive(Template)]
 #[template(path = "respec.html", escape = "none")]
@@ -18,38 +13,18 @@ struct Respec {
     content: String,
 }
 
-pub fn build(specification: &Specification, content: &str, output_file: Option<PathBuf>) {
-    let output_file = output_file.unwrap_or_else(|| PathBuf::from("specification.html"));
+/// Converts the markdown specification to a respec HTML page
+pub fn to_respec(specification: &Specification, content: &str) -> String {
+    //~ - converts markdown content to pure HTML, with an anchor on each header
+    //~   (code blocks with line numbers or highlighted lines are rendered as such)
+    let arena = Arena::new();
+    let options = formats::comrak_options(Some("".to_string()));
+    let root = parse_document(&arena, content, &options);
+    formats::render_code_options(root);
 
-    //~ - converts markdown content to pure HTML
-    let content = markdown_to_html(
-        content,
-        &ComrakOptions {
-            extension: ComrakExtensionOptions {
-                strikethrough: true,
-                tagfilter: true,
-                table: true,
-                autolink: true,
-                tasklist: true,
-                superscript: true,
-                header_ids: None,
-                footnotes: true,
-                description_lists: true,
-                front_matter_delimiter: None,
-            },
-            parse: ComrakParseOptions {
-                smart: true,
-                default_info_string: None,
-            },
-            render: ComrakRenderOptions {
-                hardbreaks: false,
-                github_pre_lang: true,
-                width: 0,
-                unsafe_: true, // it's our spec afterall
-                escape: false,
-            },
-        },
-    );
+    let mut html = vec![];
+    format_html(root, &options, &mut html).unwrap();
+    let content = String::from_utf8(html).unwrap();
 
     //~ - produces the HTML output
     let html_page = Respec {
@@ -71,7 +46,5 @@ pub fn build(specification: &Specification, content: &str, output_file: Option<P
         content,
     };
 
-    let mut file = File::create(&output_file).unwrap_or_else(|e| panic!("{}", e));
-    let _ = write!(&mut file, "{}", html_page.render().unwrap()).unwrap();
-    println!("\n=> html output saved at {}", output_file.display());
+    html_page.render().unwrap()
 }
@@ -0,0 +1,392 @@
+use comrak::{
+    nodes::{AstNode, ListType, NodeHtmlBlock, NodeValue},
+    parse_document, Anchorizer, Arena,
+};
+use miette::{IntoDiagnostic, Result, WrapErr};
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fmt::Write as FmtWrite,
+    fs,
+    process::Command,
+};
+
+use crate::{errors::SpecError, formats, toml_parser::Specification};
+
+/// Converts the markdown specification to a PDF, by compiling its Typst version with the `typst` CLI
+pub fn to_pdf(specification: &Specification, content: &str) -> Result<Vec<u8>> {
+    //~ - the PDF is produced by [typst](https://github.com/typst/typst),
+    //~   which needs to be installed
+    // a fresh directory, removed when it's dropped (including on errors)
+    let temp_dir = tempfile::tempdir()
+        .into_diagnostic()
+        .wrap_err("could not create a temporary directory")?;
+    let typst_file = temp_dir.path().join("specification.typ");
+    let pdf_file = temp_dir.path().join("specification.pdf");
+    fs::write(&typst_file, to_typst(specification, content))
+        .into_diagnostic()
+        .wrap_err_with(|| format!("could not write {}", typst_file.display()))?;
+
+    let res = Command::new("typst")
+        .arg("compile")
+        .arg(&typst_file)
+        .arg(&pdf_file)
+        .output()
+        .map_err(|_| SpecError::MissingTypesetter)?;
+    if !res.status.success() {
+        return Err(SpecError::TypesettingFailed(
+            String::from_utf8_lossy(&res.stderr).trim().to_string(),
+        )
+        .into());
+    }
+
+    fs::read(&pdf_file)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("could not read {}", pdf_file.display()))
+}
+
+/// Converts the markdown specification to a Typst document
+pub fn to_typst(specification: &Specification, content: &str) -> String {
+    let metadata = &specification.metadata;
+    let mut typst = String::new();
+
+    //~ - the Typst document starts with a title page,
+    //~   filled with the metadata of the specification
+    let authors: Vec<_> = metadata.authors.iter().map(|a| string(a)).collect();
+    writeln!(
+        typst,
+        "#set document(title: {}, author: ({}))",
+        string(&metadata.name),
+        authors.iter().map(|a| format!("{a},")).collect::<String>()
+    )
+    .unwrap();
+    writeln!(typst, "#set page(numbering: \"1\")").unwrap();
+    writeln!(typst, "#set heading(numbering: \"1.1\")").unwrap();
+    writeln!(typst, "#show raw.where(block: true): block.with(fill: luma(245), inset: 8pt, radius: 4pt, width: 100%)").unwrap();
+    writeln!(
+        typst,
+        "#show figure.where(kind: raw): set block(breakable: true)"
+    )
+    .unwrap();
+    writeln!(typst).unwrap();
+
+    writeln!(typst, "#page(numbering: none, align(center + horizon)[").unwrap();
+    writeln!(
+        typst,
+        "  #text(size: 24pt, weight: \"bold\", {})",
+        string(&metadata.name)
+    )
+    .unwrap();
+    if let Some(version) = &metadata.version {
+        writeln!(
+            typst,
+            "\n  #text(size: 14pt, {})",
+            string(&format!("Version {version}"))
+        )
+        .unwrap();
+    }
+    if !metadata.authors.is_empty() {
+        writeln!(
+            typst,
+            "\n  #text(size: 12pt, {})",
+            string(&metadata.authors.join(", "))
+        )
+        .unwrap();
+    }
+    if let Some(description) = &metadata.description {
+        writeln!(
+            typst,
+            "\n  #v(2em)\n  #block(width: 80%, {})",
+            string(description)
+        )
+        .unwrap();
+    }
+    writeln!(typst, "])").unwrap();
+    writeln!(typst).unwrap();
+
+    //~ - followed by a table of contents
+    writeln!(typst, "#outline()\n#pagebreak()\n").unwrap();
+
+    //~ - followed by the content, where headers are numbered and code blocks are numbered listings
+    let arena = Arena::new();
+    let root = parse_document(&arena, content, &formats::comrak_options(None));
+
+    // headers are labeled with the same anchors as in HTML, so that links to them keep working
+    let mut footnotes = HashMap::new();
+    let mut labels = VecDeque::new();
+    let mut anchorizer = Anchorizer::new();
+    for node in root.descendants() {
+        match &node.data.borrow().value {
+            NodeValue::FootnoteDefinition(name) => {
+                footnotes.insert(name.clone(), node);
+            }
+            NodeValue::Heading(_) => {
+                let mut text = vec![];
+                formats::collect_text(node, &mut text);
+                labels.push_back(anchorizer.anchorize(String::from_utf8_lossy(&text).to_string()));
+            }
+            _ => (),
+        }
+    }
+
+    // the title page already has the name of the specification
+    if let Some(first) = root.first_child() {
+        let is_title = matches!(&first.data.borrow().value, NodeValue::Heading(h) if h.level == 1);
+        let mut text = vec![];
+        formats::collect_text(first, &mut text);
+        if is_title && String::from_utf8_lossy(&text).trim() == metadata.name.trim() {
+            first.detach();
+            labels.pop_front();
+        }
+    }
+
+    // the highest headers become top-level sections
+    let top_level = root
+        .descendants()
+        .filter_map(|node| match &node.data.borrow().value {
+            NodeValue::Heading(heading) => Some(heading.level),
+            _ => None,
+        })
+        .min()
+        .unwrap_or(1);
+
+    let mut writer = Writer {
+        output: typst,
+        footnotes,
+        known_labels: labels
+            .iter()
+            .cloned()
+            .chain(formats::anchors(content).into_iter().map(|(_, id)| id))
+            .collect(),
+        labels,
+        level_offset: top_level - 1,
+    };
+    writer.children(root);
+
+    writer.output
+}
+
+/// Converts markdown nodes to Typst markup.
+/// Inline elements are written as function calls terminated by a `;`,
+/// so that the text following them can't be mistaken for arguments.
+struct Writer<'a> {
+    output: String,
+    footnotes: HashMap<Vec<u8>, &'a AstNode<'a>>,
+    /// the labels of the headers that haven't been written yet
+    labels: VecDeque<String>,
+    /// the labels of all the headers
+    known_labels: HashSet<String>,
+    /// what to subtract to the level of headers
+    level_offset: u32,
+}
+
+impl<'a> Writer<'a> {
+    fn children(&mut self, node: &'a AstNode<'a>) {
+        for child in node.children() {
+            self.node(child);
+        }
+    }
+
+    /// writes the children of a node as a content block (`[...]`)
+    fn content(&mut self, node: &'a AstNode<'a>) {
+        self.output.push('[');
+        self.children(node);
+        self.output.push(']');
+    }
+
+    fn node(&mut self, node: &'a AstNode<'a>) {
+        let value = node.data.borrow().value.clone();
+        match value {
+            NodeValue::Document => self.children(node),
+            NodeValue::FrontMatter(_) | NodeValue::FootnoteDefinition(_) => (),
+            // only the anchors of the HTML are kept, as labels
+            NodeValue::HtmlBlock(NodeHtmlBlock { literal, .. })
+            | NodeValue::HtmlInline(literal) => {
+                for (_, id) in formats::anchors(&String::from_utf8_lossy(&literal)) {
+                    write!(self.output, "#metadata(none) <{id}>").unwrap();
+                }
+            }
+            NodeValue::Paragraph => {
+                self.children(node);
+                self.output.push_str("\n\n");
+            }
+            NodeValue::Heading(heading) => {
+                let level = heading.level - self.level_offset;
+                write!(self.output, "#heading(level: {level})").unwrap();
+                self.content(node);
+                if let Some(label) = self.labels.pop_front() {
+                    write!(self.output, " <{label}>").unwrap();
+                }
+                self.output.push_str("\n\n");
+            }
+            NodeValue::BlockQuote => {
+                self.output.push_str("#quote(block: true)");
+                self.content(node);
+                self.output.push_str("\n\n");
+            }
+            NodeValue::List(list) => {
+                match list.list_type {
+                    ListType::Bullet => self.output.push_str("#list("),
+                    ListType::Ordered => {
+                        write!(self.output, "#enum(start: {}, ", list.start).unwrap()
+                    }
+                }
+                for item in node.children() {
+                    self.content(item);
+                    self.output.push_str(", ");
+                }
+                self.output.push_str(")\n\n");
+            }
+            NodeValue::Item(_) => self.children(node),
+            NodeValue::TaskItem(checked) => {
+                self.output.push_str(if checked { "☑ " } else { "☐ " });
+                self.children(node);
+            }
+            NodeValue::DescriptionList => {
+                self.output.push_str("#terms(");
+                for item in node.children() {
+                    self.output.push('(');
+                    for part in item.children() {
+                        self.content(part);
+                        self.output.push_str(", ");
+                    }
+                    self.output.push_str("), ");
+                }
+                self.output.push_str(")\n\n");
+            }
+            NodeValue::DescriptionItem(_)
+            | NodeValue::DescriptionTerm
+            | NodeValue::DescriptionDetails => self.children(node),
+            NodeValue::CodeBlock(code_block) => {
+                let info = String::from_utf8_lossy(&code_block.info);
+                let lang = info.split_whitespace().next();
+                let code = String::from_utf8_lossy(&code_block.literal);
+                let code = code.strip_suffix('\n').unwrap_or(&code);
+
+                self.output
+                    .push_str("#figure(kind: raw, supplement: [Listing], raw(block: true, ");
+                if let Some(lang) = lang {
+                    write!(self.output, "lang: {}, ", string(lang)).unwrap();
+                }
+                writeln!(self.output, "{}))\n", string(code)).unwrap();
+            }
+            NodeValue::ThematicBreak => self.output.push_str("#line(length: 100%)\n\n"),
+            NodeValue::Table(alignments) => {
+                write!(self.output, "#table(columns: {}, ", alignments.len()).unwrap();
+                for row in node.children() {
+                    let header = matches!(row.data.borrow().value, NodeValue::TableRow(true));
+                    for cell in row.children() {
+                        if header {
+                            self.output.push_str("strong");
+                        }
+                        self.content(cell);
+                        self.output.push_str(", ");
+                    }
+                }
+                self.output.push_str(")\n\n");
+            }
+            NodeValue::TableRow(_) | NodeValue::TableCell => self.children(node),
+            NodeValue::Text(text) => self
+                .output
+                .push_str(&escape(&String::from_utf8_lossy(&text))),
+            NodeValue::SoftBreak => self.output.push(' '),
+            NodeValue::LineBreak => self.output.push_str("#linebreak();"),
+            NodeValue::Code(code) => {
+                let code = String::from_utf8_lossy(&code.literal);
+                write!(self.output, "#raw({});", string(&code)).unwrap();
+            }
+            NodeValue::Emph => {
+                self.output.push_str("#emph");
+                self.content(node);
+                self.output.push(';');
+            }
+            NodeValue::Strong => {
+                self.output.push_str("#strong");
+                self.content(node);
+                self.output.push(';');
+            }
+            NodeValue::Strikethrough => {
+                self.output.push_str("#strike");
+                self.content(node);
+                self.output.push(';');
+            }
+            NodeValue::Superscript => {
+                self.output.push_str("#super");
+                self.content(node);
+                self.output.push(';');
+            }
+            NodeValue::Link(link) | NodeValue::Image(link) => {
+                let url = String::from_utf8_lossy(&link.url);
+                match url.strip_prefix('#') {
+                    // links to headers
+                    Some(label) if self.known_labels.contains(label) => {
+                        write!(self.output, "#link(label({}))", string(label)).unwrap();
+                    }
+                    // links to unknown anchors would fail to compile
+                    Some(_) => {
+                        self.children(node);
+                        return;
+                    }
+                    None => write!(self.output, "#link({})", string(&url)).unwrap(),
+                }
+                self.content(node);
+                self.output.push(';');
+            }
+            NodeValue::FootnoteReference(name) => {
+                if let Some(definition) = self.footnotes.get(&name).copied() {
+                    self.output.push_str("#footnote");
+                    self.content(definition);
+                    self.output.push(';');
+                }
+            }
+        }
+    }
+}
+
+/// Escapes text to be used in Typst markup
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(
+            c,
+            '\\' | '#'
+                | '*'
+                | '_'
+                | '`'
+                | '$'
+                | '<'
+                | '>'
+                | '@'
+                | '['
+                | ']'
+                | '~'
+                | '/'
+                | '='
+                | '-'
+                | '+'
+                | '"'
+                | '\''
+        ) {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+/// Writes a Typst string literal
+fn string(text: &str) -> String {
+    let mut literal = String::with_capacity(text.len() + 2);
+    literal.push('"');
+    for c in text.chars() {
+        match c {
+            '\\' => literal.push_str("\\\\"),
+            '"' => literal.push_str("\\\""),
+            '\n' => literal.push_str("\\n"),
+            '\r' => literal.push_str("\\r"),
+            '\t' => literal.push_str("\\t"),
+            c => literal.push(c),
+        }
+    }
+    literal.push('"');
+    literal
+}
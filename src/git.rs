@@ -1,32 +1,172 @@
-#![allow(dead_code, unused_variables)]
-
-use std::path::Path;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
-fn get_github_url(filepath: &Path, line: usize) -> Option<String> {
-    let local_repo = get_local_repo_path()?;
-    let github_repo = get_github_repo()?;
+/// The code hosting services we know how to create permalinks for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Host {
+    GitHub,
+    GitLab,
+}
+
+/// A remote repository, as found in `git remote -v`
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Remote {
+    host: Host,
+    /// the web URL of the repository (e.g. `https://github.com/mimoo/cargo-specification`)
+    web_url: String,
+}
 
-    None
+/// A local git repository that has a known remote,
+/// used to create permalinks to the code.
+#[derive(Debug)]
+pub struct Repository {
+    /// path to the root of the local repository
+    root: PathBuf,
+    /// the remote we link to
+    remote: Remote,
+    /// the last commit that changed the file,
+    /// so that permalinks don't change with every commit of the repository
+    commit: String,
 }
 
-/// runs `git remote -v` and checks if it's a github URL
-fn get_github_repo() -> Option<String> {
-    let res = Command::new("git").args(&["remote", "-v"]).output().ok()?;
+impl Repository {
+    /// Returns the repository that contains `filepath`,
+    /// if it is a git repository with a GitHub or GitLab remote,
+    /// and if the file is committed as it is (otherwise, its lines don't match the ones of any commit).
+    pub fn for_file(filepath: &Path) -> Option<Self> {
+        let dir = filepath.parent()?;
+        let root = git(dir, &["rev-parse", "--show-toplevel"])?;
+        let root = std::fs::canonicalize(root).ok()?;
+        let remote = get_remote(dir)?;
+        let filepath = filepath.to_string_lossy();
+        let status = git(dir, &["status", "--porcelain", "--", &filepath])?;
+        if !status.is_empty() {
+            return None;
+        }
+        let commit = git(dir, &["log", "-1", "--format=%H", "--", &filepath])
+            .filter(|commit| !commit.is_empty())?;
 
-    if !res.status.success() {
+        Some(Self {
+            root,
+            remote,
+            commit,
+        })
+    }
+
+    /// Returns a permalink to the lines `start..=end` (starting at 1) of `filepath`.
+    pub fn permalink(&self, filepath: &Path, start: usize, end: usize) -> Option<String> {
+        let filepath = std::fs::canonicalize(filepath).ok()?;
+        let relative = filepath.strip_prefix(&self.root).ok()?;
+        let relative: Vec<_> = relative
+            .components()
+            .map(|c| c.as_os_str().to_string_lossy())
+            .collect();
+        let relative = relative.join("/");
+
+        let Remote { host, web_url } = &self.remote;
+        let commit = &self.commit;
+        let url = match host {
+            Host::GitHub if start == end => format!("{web_url}/blob/{commit}/{relative}#L{start}"),
+            Host::GitHub => format!("{web_url}/blob/{commit}/{relative}#L{start}-L{end}"),
+            Host::GitLab if start == end => {
+                format!("{web_url}/-/blob/{commit}/{relative}#L{start}")
+            }
+            Host::GitLab => format!("{web_url}/-/blob/{commit}/{relative}#L{start}-{end}"),
+        };
+
+        Some(url)
+    }
+}
+
+/// runs `git remote -v` and returns the first GitHub or GitLab remote
+/// (preferring `origin` if there's one)
+fn get_remote(dir: &Path) -> Option<Remote> {
+    let res = git(dir, &["remote", "-v"])?;
+
+    // each line looks like `origin	git@github.com:mimoo/cargo-specification.git (fetch)`
+    let mut remotes = res.lines().filter_map(|line| {
+        let mut parts = line.split_whitespace();
+        let name = parts.next()?;
+        let url = parts.next()?;
+        Some((name, parse_remote_url(url)?))
+    });
+
+    let first = remotes.next()?;
+    if first.0 == "origin" {
+        return Some(first.1);
+    }
+
+    let origin = remotes.find(|(name, _)| *name == "origin");
+    Some(origin.unwrap_or(first).1)
+}
+
+/// Parses the URL of a remote. It can be an SSH URL
+/// (`git@github.com:owner/repo.git` or `ssh://git@github.com/owner/repo.git`)
+/// or an HTTPS URL (`https://github.com/owner/repo.git`).
+/// The port of HTTP(S) remotes is kept (e.g. `https://gitlab.example.com:8443/owner/repo`),
+/// but the port of SSH remotes isn't the one of the web interface.
+fn parse_remote_url(url: &str) -> Option<Remote> {
+    let scheme = ["https://", "http://", "ssh://", "git://"]
+        .into_iter()
+        .find_map(|scheme| Some((scheme, url.strip_prefix(scheme)?)));
+
+    let mut web_scheme = "https://";
+    let (host, path) = if let Some((scheme, rest)) = scheme {
+        // [user@]host[:port]/owner/repo
+        let (authority, path) = rest.split_once('/')?;
+        let host = authority.rsplit('@').next()?;
+        let host = match scheme {
+            "https://" | "http://" => {
+                web_scheme = scheme;
+                host
+            }
+            _ => host.split(':').next()?,
+        };
+        (host, path)
+    } else {
+        // [user@]host:owner/repo
+        let (authority, path) = url.split_once(':')?;
+        if authority.contains('/') {
+            // this is a local path
+            return None;
+        }
+        let host = authority.rsplit('@').next()?;
+        (host, path)
+    };
+
+    let host = host.to_lowercase();
+    let path = path.trim_matches('/');
+    let path = path.strip_suffix(".git").unwrap_or(path);
+    if host.is_empty() || path.is_empty() {
         return None;
     }
 
-    let res = String::from_utf8(res.stdout).ok()?;
+    let name = host.split(':').next().unwrap_or_default();
+    let kind = if name.contains("github") {
+        Host::GitHub
+    } else if name.contains("gitlab") {
+        Host::GitLab
+    } else {
+        return None;
+    };
+
+    Some(Remote {
+        host: kind,
+        web_url: format!("{web_scheme}{host}/{path}"),
+    })
+}
 
-    None
+/// runs `git rev-parse --show-toplevel` to get filepath of the root of the repository containing `dir`
+pub fn get_local_repo_path(dir: &Path) -> Option<String> {
+    git(dir, &["rev-parse", "--show-toplevel"])
 }
 
-/// runs `git rev-parse --show-toplevel` to get filepath of root
-pub fn get_local_repo_path() -> Option<String> {
+/// runs a git command in `dir` and returns its (trimmed) output, if successful
+fn git(dir: &Path, args: &[&str]) -> Option<String> {
     let res = Command::new("git")
-        .args(&["rev-parse", "--show-toplevel"])
+        .arg("-C")
+        .arg(dir)
+        .args(args)
         .output()
         .ok()?;
 
@@ -36,5 +176,5 @@ pub fn get_local_repo_path() -> Option<String> {
 
     let res = String::from_utf8(res.stdout).ok()?;
 
-    Some(res)
+    Some(res.trim().to_string())
 }
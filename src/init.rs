@@ -2,6 +2,7 @@ use crate::{
     errors::SpecError,
     toml_parser::{Config, Metadata, Specification},
 };
+use indexmap::IndexMap;
 use miette::{IntoDiagnostic, Result, WrapErr};
 use std::{
     collections::HashMap,
@@ -27,7 +28,7 @@ pub fn init(name: Option<String>, path: PathBuf) -> Result<()> {
         match path.file_name() {
             Some(dir_name) => dir_name.to_string_lossy().to_string(),
             None => {
-                return Err(SpecError::BadPath(path)).into_diagnostic();
+                return Err(SpecError::BadPath(path).into());
             }
         }
     };
@@ -49,7 +50,7 @@ pub fn init(name: Option<String>, path: PathBuf) -> Result<()> {
             let template_file_detected =
                 dir_entry.file_name().to_string_lossy() == DEFAULT_TEMPLATE;
             if spec_file_detected || template_file_detected {
-                return Err(SpecError::SpecAlreadyExists(path)).into_diagnostic();
+                return Err(SpecError::SpecAlreadyExists(path).into());
             }
         }
     }
@@ -70,11 +71,15 @@ pub fn init(name: Option<String>, path: PathBuf) -> Result<()> {
     };
     let config = Config {
         template: DEFAULT_TEMPLATE.to_string(),
+        ..Default::default()
     };
     let specification = Specification {
         metadata,
         config,
-        sections: HashMap::new(),
+        sections: IndexMap::new(),
+        languages: HashMap::new(),
+        profiles: IndexMap::new(),
+        outputs: vec![],
     };
 
     let manifest_content =
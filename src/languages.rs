@@ -0,0 +1,128 @@
+use std::collections::HashMap;
+
+use crate::toml_parser::Language;
+
+/// A language known by cargo-spec:
+/// its file extensions, its single-line comment delimiter,
+/// its block comment delimiters, and its name in code fences.
+type Builtin = (
+    &'static [&'static str],
+    Option<&'static str>,
+    Option<(&'static str, &'static str)>,
+    &'static str,
+);
+
+const C_BLOCK: Option<(&str, &str)> = Some(("/*", "*/"));
+
+//~ The languages supported out of the box are listed below,
+//~ as (extensions, single-line comment delimiter, block comment delimiters, code fence name):
+//~
+//~ spec:startcode
+const BUILTINS: &[Builtin] = &[
+    (&["rs"], Some("//"), C_BLOCK, "rust"),
+    (&["c", "h"], Some("//"), C_BLOCK, "c"),
+    (
+        &["cpp", "cc", "cxx", "hpp", "hh", "hxx"],
+        Some("//"),
+        C_BLOCK,
+        "cpp",
+    ),
+    (&["cs"], Some("//"), C_BLOCK, "csharp"),
+    (&["go"], Some("//"), C_BLOCK, "go"),
+    (&["java"], Some("//"), C_BLOCK, "java"),
+    (&["kt", "kts"], Some("//"), C_BLOCK, "kotlin"),
+    (&["scala", "sc"], Some("//"), C_BLOCK, "scala"),
+    (&["swift"], Some("//"), C_BLOCK, "swift"),
+    (&["dart"], Some("//"), C_BLOCK, "dart"),
+    (
+        &["js", "mjs", "cjs", "jsx"],
+        Some("//"),
+        C_BLOCK,
+        "javascript",
+    ),
+    (
+        &["ts", "mts", "cts", "tsx"],
+        Some("//"),
+        C_BLOCK,
+        "typescript",
+    ),
+    (&["php"], Some("//"), C_BLOCK, "php"),
+    (&["proto"], Some("//"), C_BLOCK, "proto"),
+    (&["sol"], Some("//"), C_BLOCK, "solidity"),
+    (&["move"], Some("//"), C_BLOCK, "move"),
+    (&["cairo"], Some("//"), None, "cairo"),
+    (&["circom"], Some("//"), C_BLOCK, "circom"),
+    (&["zig"], Some("//"), None, "zig"),
+    (&["scss", "less"], Some("//"), C_BLOCK, "scss"),
+    (&["css"], None, C_BLOCK, "css"),
+    (&["py", "pyi"], Some("#"), None, "python"),
+    (&["rb"], Some("#"), None, "ruby"),
+    (&["sh", "bash", "zsh"], Some("#"), None, "sh"),
+    (&["ps1"], Some("#"), Some(("<#", "#>")), "powershell"),
+    (&["pl", "pm"], Some("#"), None, "perl"),
+    (&["r"], Some("#"), None, "r"),
+    (&["jl"], Some("#"), Some(("#=", "=#")), "julia"),
+    (&["ex", "exs"], Some("#"), None, "elixir"),
+    (&["nix"], Some("#"), C_BLOCK, "nix"),
+    (&["toml"], Some("#"), None, "toml"),
+    (&["yaml", "yml"], Some("#"), None, "yaml"),
+    (&["cmake"], Some("#"), None, "cmake"),
+    (&["sql"], Some("--"), C_BLOCK, "sql"),
+    (&["lua"], Some("--"), Some(("--[[", "]]")), "lua"),
+    (&["hs", "lhs"], Some("--"), Some(("{-", "-}")), "haskell"),
+    (&["elm"], Some("--"), Some(("{-", "-}")), "elm"),
+    (&["adb", "ads"], Some("--"), None, "ada"),
+    (&["ml", "mli"], None, Some(("(*", "*)")), "ocaml"),
+    (
+        &["fs", "fsi", "fsx"],
+        Some("//"),
+        Some(("(*", "*)")),
+        "fsharp",
+    ),
+    (&["erl", "hrl"], Some("%"), None, "erlang"),
+    (&["tex", "sty"], Some("%"), None, "latex"),
+    (&["clj", "cljs", "cljc", "edn"], Some(";"), None, "clojure"),
+    (&["lisp", "el", "scm", "rkt"], Some(";"), None, "lisp"),
+    (&["asm", "s"], Some(";"), None, "asm"),
+    (&["vim"], Some("\""), None, "vim"),
+    (&["html", "htm"], None, Some(("<!--", "-->")), "html"),
+    (&["xml", "svg"], None, Some(("<!--", "-->")), "xml"),
+];
+//~ spec:endcode
+//~
+//~ Other languages can be configured in the `[languages]` table of the specification file,
+//~ which maps a file extension to its delimiters and code fence name
+//~ (any field that is not set falls back to the built-in language, if any).
+//~ Files with an unknown extension use `//` comments.
+
+/// Returns how spec comments are written in files with the given extension
+pub fn find(extension: &str, custom: &HashMap<String, Language>) -> Language {
+    let builtin = BUILTINS
+        .iter()
+        .find(|(extensions, _, _, _)| extensions.contains(&extension))
+        .map(|(_, line_comment, block_comment, fence)| Language {
+            line_comment: line_comment.map(str::to_string),
+            block_comment: block_comment.map(|(start, end)| (start.to_string(), end.to_string())),
+            fence: Some(fence.to_string()),
+        });
+
+    let mut language = match (custom.get(extension), builtin) {
+        (Some(custom), Some(builtin)) => Language {
+            line_comment: custom.line_comment.clone().or(builtin.line_comment),
+            block_comment: custom.block_comment.clone().or(builtin.block_comment),
+            fence: custom.fence.clone().or(builtin.fence),
+        },
+        (Some(custom), None) => custom.clone(),
+        (None, Some(builtin)) => builtin,
+        (None, None) => Language::default(),
+    };
+
+    if language.line_comment.is_none() && language.block_comment.is_none() {
+        language.line_comment = Some("//".to_string());
+    }
+    if language.fence.is_none() {
+        language.fence = Some(extension.to_string());
+    }
+
+    language
+}
@@ -1,7 +1,16 @@
 pub mod build;
 mod comment_parser;
 mod errors;
+mod features;
 mod formats;
 mod git;
 pub mod init;
-mod toml_parser;
+mod languages;
+mod outline;
+mod references;
+mod requirements;
+mod rust_parser;
+mod rustdoc;
+mod template;
+pub mod toml_parser;
+mod traceability;
@@ -1,5 +1,5 @@
 use cargo_spec::{
-    build::{build, watch, OutputFormat},
+    build::{build_targets, check, trace, watch_targets, Edition, OutputFormat, Target},
     init::{init, new, DEFAULT_MANIFEST, DEFAULT_TEMPLATE},
 };
 use clap::{Args, Parser, Subcommand};
@@ -34,6 +34,38 @@ enum Spec {
     /// Watches any listed files in the specification toml file and
     /// re-create the specification on any changes.
     Watch(Opt),
+
+    /// Checks that the specification file is up to date,
+    /// and exits with an error (and a diff) if it isn't.
+    Check(Opt),
+
+    /// Writes the traceability matrix between the requirements and the tests,
+    /// as a markdown table and as JSON.
+    Trace {
+        /// The path to the specification toml file (defaults to Specification.toml).
+        #[clap(short, long, parse(from_os_str), value_name = "SPEC_PATH")]
+        specification_path: Option<PathBuf>,
+
+        /// The path to the markdown table to write (defaults to traceability.md)
+        #[clap(short, long, parse(from_os_str), value_name = "OUTPUT_FILE")]
+        output_file: Option<PathBuf>,
+
+        /// The path to the JSON file to write (defaults to traceability.json)
+        #[clap(long, parse(from_os_str), value_name = "JSON_FILE")]
+        json_file: Option<PathBuf>,
+
+        /// Exits with an error if a requirement isn't covered by any test
+        #[clap(long)]
+        strict: bool,
+
+        /// The profile of the specification file to build the specification with
+        #[clap(long, value_name = "PROFILE")]
+        profile: Option<String>,
+
+        /// The features to enable, separated by commas
+        #[clap(long, use_delimiter = true, value_name = "FEATURES")]
+        features: Vec<String>,
+    },
 }
 
 /// The different options that can be passed to this CLI
@@ -45,14 +77,58 @@ struct Opt {
     specification_path: Option<PathBuf>,
 
     /// The path to the specification file to write
-    /// (defaults to specification.md or specification.html)
+    /// (defaults to specification.md or specification.html,
+    /// or to the directory `book` for mdbook)
     #[clap(short, long, parse(from_os_str), value_name = "OUTPUT_FILE")]
     output_file: Option<PathBuf>,
 
-    /// The output format (defaults to markdown)
+    /// The output format (defaults to markdown).
+    /// Without any of these options, the outputs listed in the specification file are built.
     #[clap(short = 'f', long, value_name = "OUTPUT_FORMAT")]
     #[clap(arg_enum)]
     output_format: Option<OutputFormat>,
+
+    /// The profile of the specification file to build the specification with
+    /// (can be given several times to build several editions)
+    #[clap(long, value_name = "PROFILE")]
+    profile: Vec<String>,
+
+    /// The features to enable, separated by commas
+    #[clap(long, use_delimiter = true, value_name = "FEATURES")]
+    features: Vec<String>,
+}
+
+/// Returns the outputs to build: one per profile (or a single one if no profile was given),
+/// or none if no option was given, so that the outputs of the specification file are built
+fn targets(
+    output_file: Option<PathBuf>,
+    output_format: Option<OutputFormat>,
+    profiles: Vec<String>,
+    features: Vec<String>,
+) -> Vec<Target> {
+    if output_file.is_none()
+        && output_format.is_none()
+        && profiles.is_empty()
+        && features.is_empty()
+    {
+        return vec![];
+    }
+
+    let editions: Vec<_> = match profiles.is_empty() {
+        true => vec![Edition {
+            profile: None,
+            features,
+        }],
+        false => profiles
+            .into_iter()
+            .map(|profile| Edition {
+                profile: Some(profile),
+                features: features.clone(),
+            })
+            .collect(),
+    };
+    let output_format = output_format.unwrap_or(OutputFormat::Markdown);
+    Target::editions(output_format, output_file, &editions)
 }
 
 fn main() -> Result<()> {
@@ -79,15 +155,18 @@ fn main() -> Result<()> {
         }
 
         //~   a. the `Build` mode builds the specification
+        //~      (in the format given on the command line, or else in each format listed in the specification file)
         Spec::Build(Opt {
             specification_path,
             output_file,
             output_format,
+            profile,
+            features,
         }) => {
             let toml_spec = specification_path.unwrap_or_else(|| PathBuf::from(DEFAULT_MANIFEST));
-            let output_format = output_format.unwrap_or(OutputFormat::Markdown);
+            let targets = targets(output_file, output_format, profile, features);
 
-            let _ = build(toml_spec, output_file, output_format)?;
+            let _ = build_targets(toml_spec, &targets)?;
         }
 
         //~   b. the `Watch` mode builds the specification on every change
@@ -95,11 +174,44 @@ fn main() -> Result<()> {
             specification_path,
             output_file,
             output_format,
+            profile,
+            features,
         }) => {
             let toml_spec = specification_path.unwrap_or_else(|| PathBuf::from(DEFAULT_MANIFEST));
-            let output_format = output_format.unwrap_or(OutputFormat::Markdown);
+            let targets = targets(output_file, output_format, profile, features);
+
+            watch_targets(toml_spec, &targets);
+        }
+
+        //~   c. the `Check` mode builds the specification in memory,
+        //~      and fails if it differs from the specification file
+        Spec::Check(Opt {
+            specification_path,
+            output_file,
+            output_format,
+            profile,
+            features,
+        }) => {
+            let toml_spec = specification_path.unwrap_or_else(|| PathBuf::from(DEFAULT_MANIFEST));
+            let targets = targets(output_file, output_format, profile, features);
+
+            check(toml_spec, &targets)?;
+        }
+
+        //~   d. the `Trace` mode writes the [traceability matrix](#traceability),
+        //~      and fails if a requirement isn't covered by any test (with `--strict`)
+        Spec::Trace {
+            specification_path,
+            output_file,
+            json_file,
+            strict,
+            profile,
+            features,
+        } => {
+            let toml_spec = specification_path.unwrap_or_else(|| PathBuf::from(DEFAULT_MANIFEST));
+            let edition = Edition { profile, features };
 
-            watch(toml_spec, output_format, output_file);
+            trace(toml_spec, output_file, json_file, strict, &edition)?;
         }
     };
 
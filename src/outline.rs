@@ -0,0 +1,120 @@
+use std::collections::HashMap;
+
+use crate::formats::{self, Header};
+
+//~ If `numbering` is enabled in the `[config]` of the specification file,
+//~ the headers of the specification are numbered hierarchically (`1`, `1.2`, `1.2.3`, etc.):
+//~
+//~ 1. a level-1 header that comes first, and that is the only one of its level,
+//~    is the title of the specification and is not numbered
+//~ 2. the highest of the other headers are numbered `1`, `2`, etc.,
+//~    the headers right below them `1.1`, `1.2`, etc., and so on
+//~    (a skipped level is numbered `0`, e.g. `1.0.1`)
+//~ 3. headers nested in another block (e.g. in a list) are not numbered
+//~
+//~ Numbering a header changes its anchor (e.g. `1.2 Handshake message` has the anchor `12-handshake-message`),
+//~ but [references](#references) can still use the anchor of the unnumbered header (e.g. `{ref:handshake-message}`).
+//~
+//~ If `toc` is enabled in the `[config]`, a table of contents
+//~ (nested lists of links to the same headers) is inserted right before the first numbered header.
+//~
+
+/// Numbers the headers of the specification and inserts a table of contents, if enabled.
+/// Returns the new content, along with the anchors of the headers that were renamed (old anchor to new anchor).
+pub fn outline(content: &str, numbering: bool, toc: bool) -> (String, HashMap<String, String>) {
+    let mut content = content.to_string();
+    let mut aliases = HashMap::new();
+
+    if numbering {
+        let headers = formats::headers(&content);
+        let mut lines: Vec<String> = content.split_inclusive('\n').map(str::to_string).collect();
+        let mut counters: Vec<usize> = vec![];
+        let (base, sections) = sections(&headers);
+        for header in sections {
+            let depth = (header.level - base) as usize;
+            if counters.len() <= depth {
+                counters.resize(depth, 0);
+                counters.push(0);
+            } else {
+                counters.truncate(depth + 1);
+            }
+            counters[depth] += 1;
+
+            let number = counters
+                .iter()
+                .map(usize::to_string)
+                .collect::<Vec<_>>()
+                .join(".");
+            number_header(&mut lines[header.line], header, &number);
+        }
+        content = lines.concat();
+
+        let numbered = formats::headers(&content);
+        for (old, new) in headers.into_iter().zip(numbered) {
+            if old.id != new.id {
+                aliases.insert(old.id, new.id);
+            }
+        }
+    }
+
+    if toc {
+        let headers = formats::headers(&content);
+        let (base, sections) = sections(&headers);
+        if let Some(first) = sections.first() {
+            let mut toc = String::new();
+            for header in &sections {
+                let indent = "  ".repeat((header.level - base) as usize);
+                let title = header.title.replace('[', "\\[").replace(']', "\\]");
+                toc.push_str(&format!("{indent}- [{title}](#{})\n", header.id));
+            }
+            toc.push('\n');
+
+            let mut lines: Vec<&str> = content.split_inclusive('\n').collect();
+            if first.line > 0 && !lines[first.line - 1].trim().is_empty() {
+                toc.insert(0, '\n');
+            }
+            lines.insert(first.line, &toc);
+            content = lines.concat();
+        }
+    }
+
+    (content, aliases)
+}
+
+/// Returns the headers that are sections (all the top-level headers but the title),
+/// along with the level of the highest ones
+fn sections(headers: &[Header]) -> (u32, Vec<&Header>) {
+    let top_level: Vec<_> = headers.iter().filter(|header| header.top_level).collect();
+    let titles = top_level.iter().filter(|header| header.level == 1).count();
+    let skip = match top_level.first() {
+        Some(first) if first.level == 1 && titles == 1 => 1,
+        _ => 0,
+    };
+    let sections = top_level[skip..].to_vec();
+    let base = sections
+        .iter()
+        .map(|header| header.level)
+        .min()
+        .unwrap_or(1);
+    (base, sections)
+}
+
+/// Prefixes the title of a header with its number
+fn number_header(line: &mut String, header: &Header, number: &str) {
+    let indent = line.len() - line.trim_start().len();
+    let start = if header.setext {
+        indent
+    } else {
+        // skip the `#` and the spaces that follow
+        let hashes = line[indent..].len() - line[indent..].trim_start_matches('#').len();
+        let after = &line[indent + hashes..];
+        let spaces = after.len() - after.trim_start_matches([' ', '\t']).len();
+        if after.trim().is_empty() {
+            let end = line.trim_end().len();
+            line.insert_str(end, &format!(" {number}"));
+            return;
+        }
+        indent + hashes + spaces
+    };
+    line.insert_str(start, &format!("{number} "));
+}
@@ -0,0 +1,196 @@
+use miette::{NamedSource, Result};
+use std::{
+    collections::{HashMap, HashSet},
+    fs,
+    path::PathBuf,
+};
+
+use crate::{errors::SpecError, formats, template::did_you_mean};
+
+const REFERENCE: &str = "{ref:";
+
+/// References that are replaced by their text when they have no target
+const OPTIONAL_REFERENCE: &str = "{ref?:";
+
+//~ Anywhere in the specification (in spec comments, or in the template),
+//~ `{ref:id}` refers to a header or to an anchor created with `spec:anchor(id)`.
+//~ The `id` of a header is its anchor, the way GitHub creates them
+//~ (e.g. `handshake-message` for a `## Handshake message` header).
+//~
+//~ Once the template is rendered:
+//~
+//~ 1. the headers and anchors of the whole specification are collected
+//~ 2. each reference is replaced with a link to its target
+//~    (titled after the header, or the name of the anchor)
+//~ 3. references in code are left untouched
+//~ 4. a reference that has no target is an error,
+//~    pointing to the file where the reference was written
+//~
+//~ The text of the link can be given after the id (e.g. `{ref:handshake-message|the handshake}`).
+//~ Optional references (`{ref?:id|text}`, which is what [intra-doc links](#rustdoc) become)
+//~ can also point to the lowercased id,
+//~ and are replaced by their text (or their id) when they have no target.
+//~
+
+/// Replaces the references in the specification with links to their targets.
+/// The `aliases` are other anchors the headers can be referred to with,
+/// and the `sources` are the files the specification was built from,
+/// which are used to point to references that have no target.
+pub fn resolve(
+    content: &str,
+    aliases: &HashMap<String, String>,
+    sources: &HashSet<PathBuf>,
+) -> Result<String> {
+    // the targets of the references, as (anchor, title)
+    let mut targets = HashMap::new();
+    for header in formats::headers(content) {
+        targets
+            .entry(header.id.clone())
+            .or_insert((header.id, header.title));
+    }
+    for (_, id) in formats::anchors(content) {
+        targets.entry(id.clone()).or_insert((id.clone(), id));
+    }
+    for (alias, id) in aliases {
+        if let Some(target) = targets.get(id).cloned() {
+            targets.entry(alias.clone()).or_insert(target);
+        }
+    }
+
+    let mut resolved = String::with_capacity(content.len());
+    let mut missing = vec![];
+    let mut fence: Option<String> = None;
+    for line in content.split_inclusive('\n') {
+        // code blocks are copied as is
+        let trimmed = line.trim_start();
+        match &fence {
+            Some(open) => {
+                if trimmed.trim_end().starts_with(open.as_str())
+                    && trimmed.trim_end().chars().all(|c| open.starts_with(c))
+                {
+                    fence = None;
+                }
+                resolved.push_str(line);
+                continue;
+            }
+            None if trimmed.starts_with("```") || trimmed.starts_with("~~~") => {
+                let marker = trimmed.chars().next().unwrap_or('`');
+                fence = Some(trimmed.chars().take_while(|c| *c == marker).collect());
+                resolved.push_str(line);
+                continue;
+            }
+            None => (),
+        }
+
+        let mut rest = line;
+        while !rest.is_empty() {
+            let next_code = rest.find('`');
+            let next_ref = rest
+                .find(REFERENCE)
+                .into_iter()
+                .chain(rest.find(OPTIONAL_REFERENCE))
+                .min();
+            match (next_code, next_ref) {
+                // inline code is copied as is
+                (Some(code), reference) if reference.is_none_or(|r| code < r) => {
+                    let ticks = rest[code..].chars().take_while(|c| *c == '`').count();
+                    let delimiter = "`".repeat(ticks);
+                    let end = rest[code + ticks..]
+                        .find(&delimiter)
+                        .map_or(rest.len(), |end| code + ticks + end + ticks);
+                    resolved.push_str(&rest[..end]);
+                    rest = &rest[end..];
+                }
+                (_, Some(reference)) => {
+                    resolved.push_str(&rest[..reference]);
+                    let optional = rest[reference..].starts_with(OPTIONAL_REFERENCE);
+                    let prefix = if optional {
+                        OPTIONAL_REFERENCE
+                    } else {
+                        REFERENCE
+                    };
+                    let after = &rest[reference + prefix.len()..];
+                    let Some(end) = after.find('}') else {
+                        resolved.push_str(&rest[reference..]);
+                        break;
+                    };
+
+                    let (id, text) = match after[..end].split_once('|') {
+                        Some((id, text)) => (id.trim(), Some(text)),
+                        None => (after[..end].trim(), None),
+                    };
+                    let target = match optional {
+                        true => targets.get(id).or_else(|| targets.get(&id.to_lowercase())),
+                        false => targets.get(id),
+                    };
+                    match target {
+                        Some((anchor, title)) => {
+                            let title = match text {
+                                Some(text) => text.to_string(),
+                                None => title.replace('[', "\\[").replace(']', "\\]"),
+                            };
+                            resolved.push_str(&format!("[{title}](#{anchor})"));
+                        }
+                        None if optional => resolved.push_str(text.unwrap_or(id)),
+                        None => {
+                            missing.push(id.to_string());
+                            resolved.push_str(&rest[reference..reference + prefix.len() + end + 1]);
+                        }
+                    }
+                    rest = &after[end + 1..];
+                }
+                _ => {
+                    resolved.push_str(rest);
+                    break;
+                }
+            }
+        }
+    }
+
+    if missing.is_empty() {
+        return Ok(resolved);
+    }
+
+    let ids: Vec<_> = targets.keys().map(String::as_str).collect();
+    let mut problems: Vec<_> = missing
+        .into_iter()
+        .map(|id| {
+            let help = match did_you_mean(&id, &ids) {
+                Some(candidate) => format!("did you mean `{candidate}`?"),
+                None => "references can point to headers (e.g. `{ref:handshake-message}` for `## Handshake message`) or to a `spec:anchor(id)`".to_string(),
+            };
+            match locate(&id, sources) {
+                Some((path, source, bad_bit)) => SpecError::MissingReference {
+                    bad_bit,
+                    id,
+                    help,
+                    src: NamedSource::new(path.display().to_string(), source),
+                },
+                None => SpecError::MissingReferenceTarget(id, help),
+            }
+        })
+        .collect();
+
+    if problems.len() == 1 {
+        Err(problems.remove(0).into())
+    } else {
+        Err(SpecError::MissingReferences { problems }.into())
+    }
+}
+
+/// Finds the file where a reference was written, and where it is in the file
+/// (its offset and length, including the text of the link if it has one)
+fn locate(id: &str, sources: &HashSet<PathBuf>) -> Option<(PathBuf, String, (usize, usize))> {
+    let reference = format!("{REFERENCE}{id}");
+    let mut sources: Vec<_> = sources.iter().collect();
+    sources.sort();
+    sources.into_iter().find_map(|path| {
+        let source = fs::read_to_string(path).ok()?;
+        let offset = source.match_indices(&reference).find_map(|(offset, _)| {
+            let next = source[offset + reference.len()..].chars().next();
+            matches!(next, Some('}' | '|')).then_some(offset)
+        })?;
+        let len = source[offset..].find('}')? + 1;
+        Some((path.clone(), source, (offset, len)))
+    })
+}
@@ -0,0 +1,108 @@
+use miette::{NamedSource, Result};
+use serde::Serialize;
+use std::{
+    collections::{hash_map::Entry, HashMap},
+    fs,
+    path::PathBuf,
+};
+
+use crate::{errors::SpecError, traceability::Test};
+
+//~ Normative requirements are registered in spec comments with `spec:req(ID)`,
+//~ followed by the text of the requirement
+//~ (e.g. `//~ spec:req(ID-42) The node MUST reject blocks from the future`).
+//~ The text goes on in the spec comments that follow, up to an empty line.
+//~
+//~ 1. requirement IDs can only contain letters, digits, dashes, and underscores,
+//~    and each ID can only be registered once in the whole specification
+//~ 2. a requirement is rendered as its ID (in bold) followed by its text,
+//~    with an anchor so that it can be referred to as `{ref:ID-42}`
+//~ 3. the requirements are available in the template as `requirements`,
+//~    a list of objects with an `id`, a `text`, a `file` (relative to the specification file), and a `line`,
+//~    which can be used to produce an index of the requirements
+//~ 4. the keywords of [RFC 2119](https://www.rfc-editor.org/rfc/rfc2119) that make something mandatory or recommended
+//~    (`MUST`, `SHALL`, `REQUIRED`, `SHOULD`, and `RECOMMENDED`)
+//~    produce a warning when they are used in spec comments outside of a requirement
+//~    (except in code).
+//~    Only spec comments are checked: doc comments, markdown files, and the template aren't,
+//~    as requirements can't be registered there.
+//~
+
+/// The keywords of RFC 2119 that should only be used in a requirement
+const KEYWORDS: [&str; 5] = ["MUST", "SHALL", "REQUIRED", "SHOULD", "RECOMMENDED"];
+
+/// A normative requirement of the specification (`spec:req(ID)`)
+#[derive(Serialize, Debug, Clone)]
+pub struct Requirement {
+    pub id: String,
+    pub text: String,
+    /// the file the requirement was registered in
+    pub file: PathBuf,
+    /// the line of the `spec:req` instruction (starting at 1)
+    pub line: usize,
+    /// the offset of the ID in the file
+    #[serde(skip)]
+    pub offset: usize,
+    /// the tests covering the requirement (see [traceability](#traceability))
+    pub tests: Vec<Test>,
+}
+
+/// Returns the ID of a requirement, if it's valid
+pub fn requirement_id(argument: Option<&str>) -> Option<&str> {
+    argument.filter(|id| {
+        !id.is_empty()
+            && id
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+    })
+}
+
+/// Returns the RFC 2119 keywords used in a line (outside of inline code), along with their offsets
+pub fn keywords(line: &str) -> Vec<(usize, &'static str)> {
+    let mut found = vec![];
+    let mut in_code = false;
+    let mut word_start = None;
+    for (offset, c) in line.char_indices().chain([(line.len(), ' ')]) {
+        if c.is_alphanumeric() || c == '_' {
+            word_start.get_or_insert(offset);
+            continue;
+        }
+
+        if let Some(start) = word_start.take() {
+            let keyword = KEYWORDS
+                .iter()
+                .find(|keyword| **keyword == &line[start..offset]);
+            if let (Some(keyword), false) = (keyword, in_code) {
+                found.push((start, *keyword));
+            }
+        }
+        if c == '`' {
+            in_code = !in_code;
+        }
+    }
+    found
+}
+
+/// Makes sure that each requirement ID is only registered once
+pub fn check_duplicates(requirements: &[Requirement]) -> Result<()> {
+    let mut registered: HashMap<&str, &Requirement> = HashMap::new();
+    for requirement in requirements {
+        let first = match registered.entry(&requirement.id) {
+            Entry::Occupied(first) => *first.get(),
+            Entry::Vacant(entry) => {
+                entry.insert(requirement);
+                continue;
+            }
+        };
+
+        let source = fs::read_to_string(&requirement.file).unwrap_or_default();
+        return Err(SpecError::DuplicateRequirement {
+            id: requirement.id.clone(),
+            first: format!("{}:{}", first.file.display(), first.line),
+            src: NamedSource::new(requirement.file.display().to_string(), source),
+            bad_bit: (requirement.offset, requirement.id.len()),
+        }
+        .into());
+    }
+    Ok(())
+}
@@ -0,0 +1,457 @@
+use miette::{IntoDiagnostic, NamedSource, Result, WrapErr};
+use proc_macro2::LineColumn;
+use std::{
+    fmt::Write as FmtWrite,
+    path::{Path, PathBuf},
+};
+use syn::spanned::Spanned;
+
+use crate::{
+    comment_parser::{Block, BlockKind},
+    errors::SpecError,
+    git::Repository,
+    rustdoc,
+};
+
+/// The location of a Rust item in a source file
+#[derive(Debug, Clone)]
+pub struct ItemLocation {
+    /// where the item starts (including its attributes and doc comments)
+    start: LineColumn,
+    /// where the item ends
+    end: LineColumn,
+    /// for functions, where the signature ends
+    signature_end: Option<LineColumn>,
+    /// the lines (starting at 1) taken by the doc comments of the item
+    doc_lines: Vec<(usize, usize)>,
+    /// the doc comments of the item
+    docs: String,
+}
+
+impl ItemLocation {
+    fn new(
+        item: &impl Spanned,
+        attrs: &[syn::Attribute],
+        signature: Option<&syn::Signature>,
+    ) -> Self {
+        let span = item.span();
+        let doc_attrs: Vec<_> = attrs
+            .iter()
+            .filter_map(|attr| Some((attr, doc_string(attr)?)))
+            .collect();
+
+        Self {
+            start: span.start(),
+            end: span.end(),
+            signature_end: signature.map(|sig| sig.span().end()),
+            doc_lines: doc_attrs
+                .iter()
+                .map(|(attr, _)| (attr.span().start().line, attr.span().end().line))
+                .collect(),
+            docs: doc_attrs
+                .iter()
+                .map(|(_, doc)| doc.strip_prefix(' ').unwrap_or(doc))
+                .collect::<Vec<_>>()
+                .join("\n"),
+        }
+    }
+
+    /// The first and last lines (starting at 1) of what [Self::extract] returns
+    pub fn lines(&self) -> (usize, usize) {
+        let end = self.signature_end.unwrap_or(self.end);
+        (self.start.line, end.line)
+    }
+
+    /// The doc comments of the item
+    pub fn docs(&self) -> &str {
+        &self.docs
+    }
+
+    /// The lines (starting at 1) of the doc comments of the item
+    pub fn doc_lines(&self) -> impl Iterator<Item = usize> + '_ {
+        self.doc_lines.iter().flat_map(|(start, end)| *start..=*end)
+    }
+
+    /// Returns the source of the item, optionally without its doc comments.
+    /// Functions are cut after their signature, to leave out their body.
+    pub fn extract(&self, source: &str, with_docs: bool) -> String {
+        let (first, last) = self.lines();
+        let mut lines: Vec<_> = source
+            .lines()
+            .enumerate()
+            .skip(first - 1)
+            .take(last - first + 1)
+            .filter(|(idx, _)| {
+                with_docs
+                    || !self
+                        .doc_lines
+                        .iter()
+                        .any(|(start, end)| (*start..=*end).contains(&(idx + 1)))
+            })
+            .map(|(_, line)| line)
+            .collect();
+
+        if let (Some(end), Some(last_line)) = (self.signature_end, lines.last_mut()) {
+            *last_line = &last_line[..byte_column(last_line, end.column)];
+        }
+
+        lines.join("\n")
+    }
+}
+
+/// Returns the content of a `#[doc = "..."]` attribute (which is what doc comments are)
+fn doc_string(attr: &syn::Attribute) -> Option<String> {
+    match &attr.meta {
+        syn::Meta::NameValue(syn::MetaNameValue {
+            path,
+            value:
+                syn::Expr::Lit(syn::ExprLit {
+                    lit: syn::Lit::Str(doc),
+                    ..
+                }),
+            ..
+        }) if path.is_ident("doc") => Some(doc.value()),
+        _ => None,
+    }
+}
+
+/// All the items of a Rust file, including the ones nested in modules, traits, and impl blocks
+pub struct RustItems(Vec<ItemLocation>);
+
+impl RustItems {
+    /// Parses a Rust file
+    pub fn parse(file: &Path, source: &str) -> Result<Self> {
+        let file = parse_rust(file, source)?;
+        let mut items = vec![];
+        collect_items(&file.items, &mut items);
+        Ok(Self(items))
+    }
+
+    /// Returns the item that directly follows the given line (starting at 1),
+    /// with only blank lines, comments, and attributes in between.
+    /// If several items start on the same line, the outermost one is returned.
+    pub fn after(&self, source: &str, line: usize) -> Option<&ItemLocation> {
+        // the first line that belongs to an item, or to whatever code comes instead
+        let (idx, _) = source.lines().enumerate().skip(line).find(|(_, line)| {
+            let line = line.trim_start();
+            !(line.is_empty()
+                || line.starts_with("//")
+                || line.starts_with("/*")
+                || line.starts_with('*')
+                || line.starts_with("#["))
+        })?;
+
+        self.0
+            .iter()
+            .filter(|item| item.start.line > line && item.start.line <= idx + 1)
+            .min_by_key(|item| (item.start, std::cmp::Reverse(item.end)))
+    }
+}
+
+fn collect_items(items: &[syn::Item], locations: &mut Vec<ItemLocation>) {
+    for item in items {
+        locations.push(ItemLocation::new(
+            item,
+            item_attrs(item),
+            item_signature(item),
+        ));
+
+        match item {
+            syn::Item::Mod(item_mod) => {
+                if let Some((_, items)) = &item_mod.content {
+                    collect_items(items, locations);
+                }
+            }
+            syn::Item::Impl(item_impl) => {
+                for impl_item in &item_impl.items {
+                    let (attrs, signature) = match impl_item {
+                        syn::ImplItem::Fn(method) => (&method.attrs[..], Some(&method.sig)),
+                        syn::ImplItem::Const(item) => (&item.attrs[..], None),
+                        syn::ImplItem::Type(item) => (&item.attrs[..], None),
+                        syn::ImplItem::Macro(item) => (&item.attrs[..], None),
+                        _ => (&[][..], None),
+                    };
+                    locations.push(ItemLocation::new(impl_item, attrs, signature));
+                }
+            }
+            syn::Item::Trait(item_trait) => {
+                for trait_item in &item_trait.items {
+                    let (attrs, signature) = match trait_item {
+                        syn::TraitItem::Fn(method) if method.default.is_some() => {
+                            (&method.attrs[..], Some(&method.sig))
+                        }
+                        syn::TraitItem::Fn(method) => (&method.attrs[..], None),
+                        syn::TraitItem::Const(item) => (&item.attrs[..], None),
+                        syn::TraitItem::Type(item) => (&item.attrs[..], None),
+                        syn::TraitItem::Macro(item) => (&item.attrs[..], None),
+                        _ => (&[][..], None),
+                    };
+                    locations.push(ItemLocation::new(trait_item, attrs, signature));
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+/// Returns the attributes of an item
+fn item_attrs(item: &syn::Item) -> &[syn::Attribute] {
+    use syn::Item::*;
+    match item {
+        Const(item) => &item.attrs,
+        Enum(item) => &item.attrs,
+        ExternCrate(item) => &item.attrs,
+        Fn(item) => &item.attrs,
+        ForeignMod(item) => &item.attrs,
+        Impl(item) => &item.attrs,
+        Macro(item) => &item.attrs,
+        Mod(item) => &item.attrs,
+        Static(item) => &item.attrs,
+        Struct(item) => &item.attrs,
+        Trait(item) => &item.attrs,
+        TraitAlias(item) => &item.attrs,
+        Type(item) => &item.attrs,
+        Union(item) => &item.attrs,
+        Use(item) => &item.attrs,
+        _ => &[],
+    }
+}
+
+/// Returns the signature of an item, if it's a function
+fn item_signature(item: &syn::Item) -> Option<&syn::Signature> {
+    match item {
+        syn::Item::Fn(item_fn) => Some(&item_fn.sig),
+        _ => None,
+    }
+}
+
+/// Returns the name of an item that can be referred to by path
+fn item_name(item: &syn::Item) -> Option<String> {
+    use syn::Item::*;
+    let ident = match item {
+        Const(item) => &item.ident,
+        Enum(item) => &item.ident,
+        Fn(item) => &item.sig.ident,
+        Macro(item) => item.ident.as_ref()?,
+        Mod(item) => &item.ident,
+        Static(item) => &item.ident,
+        Struct(item) => &item.ident,
+        Trait(item) => &item.ident,
+        TraitAlias(item) => &item.ident,
+        Type(item) => &item.ident,
+        Union(item) => &item.ident,
+        _ => return None,
+    };
+    Some(ident.to_string())
+}
+
+//~ A section can also refer to a Rust item by its path (e.g. `item:crate::toml_parser::Specification`).
+//~ In this case:
+//~
+/// A Rust item found by its path in a crate
+pub struct FoundItem {
+    /// the file containing the item
+    pub file: PathBuf,
+    /// the content of that file
+    pub source: String,
+    /// the location of the item in that file
+    pub item: ItemLocation,
+    /// all the files that were read to find the item
+    pub files_read: Vec<PathBuf>,
+}
+
+/// Finds a Rust item by its path in the crate that contains `dir`
+pub fn find_item(dir: &Path, item_path: &str) -> Result<FoundItem> {
+    let not_found = |reason: String| SpecError::ItemNotFound(item_path.to_string(), reason);
+
+    //~ 1. the crate is the closest parent directory of the specification that has a `Cargo.toml`
+    //~    (its root is `src/lib.rs` or `src/main.rs`)
+    let root_file =
+        crate_root(dir).ok_or_else(|| not_found(format!("no crate found in {}", dir.display())))?;
+
+    let segments: Vec<_> = item_path
+        .strip_prefix("crate::")
+        .unwrap_or(item_path)
+        .split("::")
+        .collect();
+    let (name, modules) = segments.split_last().unwrap();
+
+    let mut file = root_file;
+    let mut source = read_file(&file)?;
+    let mut files_read = vec![file.clone()];
+    let mut parsed = parse_rust(&file, &source)?;
+
+    // the items of the current module and the directory its submodules live in
+    let mut items = parsed.items.clone();
+    let mut module_dir = file.parent().unwrap().to_path_buf();
+
+    //~ 2. each module of the path is searched in the previous one:
+    //~    it can be an inline module, or a `mod something;` declaration
+    //~    which is looked up in `something.rs`, `something/mod.rs`, or a `#[path]` attribute
+    for module in modules {
+        let item_mod = items
+            .iter()
+            .find_map(|item| match item {
+                syn::Item::Mod(item_mod) if item_mod.ident == module => Some(item_mod.clone()),
+                _ => None,
+            })
+            .ok_or_else(|| not_found(format!("module `{module}` not found")))?;
+
+        if let Some((_, content)) = item_mod.content {
+            items = content;
+            module_dir.push(module);
+            continue;
+        }
+
+        let path_attr = item_mod.attrs.iter().find_map(|attr| match &attr.meta {
+            syn::Meta::NameValue(syn::MetaNameValue {
+                path,
+                value:
+                    syn::Expr::Lit(syn::ExprLit {
+                        lit: syn::Lit::Str(path_attr),
+                        ..
+                    }),
+                ..
+            }) if path.is_ident("path") => Some(path_attr.value()),
+            _ => None,
+        });
+
+        let candidates = match path_attr {
+            Some(path_attr) => vec![module_dir.join(path_attr)],
+            None => vec![
+                module_dir.join(format!("{module}.rs")),
+                module_dir.join(module).join("mod.rs"),
+            ],
+        };
+        file = candidates
+            .into_iter()
+            .find(|candidate| candidate.is_file())
+            .ok_or_else(|| not_found(format!("no file found for module `{module}`")))?;
+
+        source = read_file(&file)?;
+        files_read.push(file.clone());
+        parsed = parse_rust(&file, &source)?;
+        items = parsed.items.clone();
+
+        module_dir = if file.file_name().is_some_and(|f| f == "mod.rs") {
+            file.parent().unwrap().to_path_buf()
+        } else {
+            file.with_extension("")
+        };
+    }
+
+    //~ 3. the item is then found in the last module
+    let item = items
+        .iter()
+        .find(|item| item_name(item).as_deref() == Some(*name))
+        .ok_or_else(|| not_found(format!("item `{name}` not found")))?;
+
+    Ok(FoundItem {
+        file,
+        source,
+        item: ItemLocation::new(item, item_attrs(item), item_signature(item)),
+        files_read,
+    })
+}
+
+impl FoundItem {
+    //~ 4. the item is rendered as its doc comment,
+    //~    followed by its code (without the doc comment, and without the body for functions),
+    //~    followed by a permalink to the code if the crate is in a GitHub or GitLab repository
+    pub fn to_markdown(&self) -> String {
+        let mut result = String::new();
+
+        let docs = rustdoc::to_markdown(self.item.docs(), &self.source);
+        if !docs.is_empty() {
+            writeln!(&mut result, "{docs}\n").unwrap();
+        }
+
+        writeln!(&mut result, "```rust").unwrap();
+        writeln!(&mut result, "{}", self.item.extract(&self.source, false)).unwrap();
+        writeln!(&mut result, "```").unwrap();
+
+        let (first_line, last_line) = self.item.lines();
+        let permalink = Repository::for_file(&self.file)
+            .and_then(|repo| repo.permalink(&self.file, first_line, last_line));
+        if let Some(permalink) = permalink {
+            writeln!(&mut result, "\n[source]({permalink})\n").unwrap();
+        }
+
+        result
+    }
+
+    /// The doc comment (if any) and the code of the item, as blocks
+    pub fn blocks(&self) -> Vec<Block> {
+        let mut blocks = vec![];
+        let block = |kind, (first_line, last_line), content: String| Block {
+            kind,
+            file: self.file.clone(),
+            first_line,
+            last_line,
+            content: content + "\n",
+        };
+
+        if let (Some((first_line, _)), Some((_, last_line))) =
+            (self.item.doc_lines.first(), self.item.doc_lines.last())
+        {
+            blocks.push(block(
+                BlockKind::Prose,
+                (*first_line, *last_line),
+                rustdoc::to_markdown(self.item.docs(), &self.source),
+            ));
+        }
+        blocks.push(block(
+            BlockKind::Code,
+            self.item.lines(),
+            self.item.extract(&self.source, false),
+        ));
+        blocks
+    }
+}
+
+/// Returns the root file of the closest crate containing `dir`
+fn crate_root(dir: &Path) -> Option<PathBuf> {
+    dir.ancestors()
+        .filter(|dir| dir.join("Cargo.toml").is_file())
+        .flat_map(|dir| [dir.join("src/lib.rs"), dir.join("src/main.rs")])
+        .find(|root| root.is_file())
+}
+
+fn read_file(file: &Path) -> Result<String> {
+    std::fs::read_to_string(file)
+        .into_diagnostic()
+        .wrap_err_with(|| format!("could not read file {}", file.display()))
+}
+
+/// Parses a Rust file, returning a diagnostic pointing at the problem if it can't be parsed
+pub fn parse_rust(file: &Path, source: &str) -> Result<syn::File> {
+    syn::parse_file(source).map_err(|e| {
+        let start = byte_offset(source, e.span().start());
+        let end = byte_offset(source, e.span().end());
+        SpecError::CantParseRust {
+            message: e.to_string(),
+            src: NamedSource::new(file.to_string_lossy(), source.to_string()),
+            bad_bit: (start, end.saturating_sub(start)),
+        }
+        .into()
+    })
+}
+
+/// Converts a [LineColumn] into a byte offset in `source`
+pub fn byte_offset(source: &str, position: LineColumn) -> usize {
+    let line_start: usize = source
+        .split_inclusive('\n')
+        .take(position.line.saturating_sub(1))
+        .map(str::len)
+        .sum();
+    let line = source[line_start..].lines().next().unwrap_or("");
+    line_start + byte_column(line, position.column)
+}
+
+/// Converts a column (in characters) into a byte offset in `line`
+fn byte_column(line: &str, column: usize) -> usize {
+    line.char_indices()
+        .nth(column)
+        .map(|(offset, _)| offset)
+        .unwrap_or(line.len())
+}
@@ -0,0 +1,342 @@
+use std::collections::{HashMap, HashSet};
+
+//~ The sections in `docs` mode (`mode = "docs"`) also extract the doc comments of Rust files:
+//~ `///` for the item that follows, and `//!` for the enclosing module.
+//~ They are converted from rustdoc to the markdown of the specification:
+//~
+//~ 1. each run of doc comments is a paragraph of its own
+//~ 2. code blocks without a language, or with rustdoc attributes (e.g. `ignore` or `no_run`), are Rust code,
+//~    and the lines that rustdoc hides in them (starting with `# `) are removed
+//~ 3. intra-doc links (e.g. ``[`Config`]``, `[the config](Config)`, or ``[`crate::toml_parser::Config`]``)
+//~    become links to the header or the anchor named after the item (e.g. `config` or `Config` for `Config`),
+//~    and are replaced by their text if there's no such target in the specification
+//~    (see [references](#references)).
+//~    Links are only intra-doc links if their target is in backticks, has a disambiguator (e.g. `struct@Config`),
+//~    is a path starting with `crate::`, `self::`, or `super::`, or starts with the name of an item of the file,
+//~    so that other links (e.g. `[guide](README)`) are kept as they are
+//~ 4. doc comments between `spec:startcode` and `spec:endcode` are part of the extracted code
+//~
+//~ The doc comments of the Rust items listed in a section (e.g. `item:crate::Spec`) are converted the same way.
+//~
+
+/// The delimiters of doc comments
+const DOC_COMMENTS: [&str; 2] = ["///", "//!"];
+
+/// The kinds of items that can prefix the target of an intra-doc link (e.g. `struct@Config`)
+const DISAMBIGUATORS: [&str; 20] = [
+    "struct",
+    "enum",
+    "trait",
+    "union",
+    "mod",
+    "module",
+    "const",
+    "constant",
+    "fn",
+    "function",
+    "method",
+    "tymethod",
+    "derive",
+    "type",
+    "value",
+    "macro",
+    "prim",
+    "primitive",
+    "field",
+    "variant",
+];
+
+/// The keywords that declare the items intra-doc links can point to
+const ITEM_KEYWORDS: [&str; 10] = [
+    "struct",
+    "enum",
+    "trait",
+    "union",
+    "mod",
+    "const",
+    "static",
+    "fn",
+    "type",
+    "macro_rules!",
+];
+
+/// What can come before the keyword of an item (e.g. `pub(crate) unsafe` in `pub(crate) unsafe fn`)
+const ITEM_QUALIFIERS: [&str; 8] = [
+    "pub", "crate", "self", "super", "in", "async", "unsafe", "default",
+];
+
+/// The attributes of rustdoc code blocks, which are still Rust code
+const RUST_ATTRIBUTES: [&str; 7] = [
+    "rust",
+    "ignore",
+    "should_panic",
+    "no_run",
+    "compile_fail",
+    "test_harness",
+    "allow_fail",
+];
+
+/// Returns the text of a doc comment (without its delimiter and the space that follows),
+/// if the line is one
+pub fn doc_comment(line: &str) -> Option<&str> {
+    let line = line.trim_start();
+    let text = DOC_COMMENTS
+        .iter()
+        .find_map(|delimiter| line.strip_prefix(delimiter))?;
+
+    // four slashes or more is a regular comment
+    if line.starts_with("///") && text.starts_with('/') {
+        return None;
+    }
+    Some(text.strip_prefix(' ').unwrap_or(text))
+}
+
+/// Converts the whole doc comment of an item to markdown
+/// (`source` is the file the item is in, to find its link reference definitions and the names of its items)
+pub fn to_markdown(docs: &str, source: &str) -> String {
+    let mut converter = DocComments::new(source);
+    docs.lines()
+        .filter_map(|line| converter.line(line))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// Converts doc comments to markdown, line by line
+pub struct DocComments {
+    /// the fence of the code block we're in, and if it contains Rust code
+    code_block: Option<(String, bool)>,
+    /// the link reference definitions (e.g. `[config]: crate::Config`), indexed by their lowercased label
+    definitions: HashMap<String, String>,
+    /// the names of the items declared in the file, which intra-doc links can point to without a path
+    names: HashSet<String>,
+}
+
+impl DocComments {
+    /// Prepares the conversion of the doc comments of a Rust file,
+    /// collecting its link reference definitions and the names of its items
+    pub fn new(source: &str) -> Self {
+        let definitions = source
+            .lines()
+            .filter_map(|line| definition(doc_comment(line).unwrap_or(line)))
+            .map(|(label, target)| (label.to_lowercase(), target.to_string()))
+            .collect();
+        Self {
+            code_block: None,
+            definitions,
+            names: source.lines().filter_map(item_name).collect(),
+        }
+    }
+
+    /// Converts a line of doc comment (without its delimiter),
+    /// or returns `None` if the line shouldn't be part of the specification
+    pub fn line(&mut self, line: &str) -> Option<String> {
+        let trimmed = line.trim_start();
+        let indent = &line[..line.len() - trimmed.len()];
+
+        if let Some((fence, rust)) = &self.code_block {
+            if trimmed.trim_end().starts_with(fence.as_str())
+                && trimmed.trim_end().chars().all(|c| fence.starts_with(c))
+            {
+                self.code_block = None;
+                return Some(line.to_string());
+            }
+
+            // lines hidden by rustdoc
+            if *rust {
+                if trimmed == "#" || trimmed.starts_with("# ") {
+                    return None;
+                }
+                if let Some(escaped) = trimmed.strip_prefix("##") {
+                    return Some(format!("{indent}#{escaped}"));
+                }
+            }
+            return Some(line.to_string());
+        }
+
+        if trimmed.starts_with("```") || trimmed.starts_with("~~~") {
+            let marker = trimmed.chars().next().unwrap();
+            let fence: String = trimmed.chars().take_while(|c| *c == marker).collect();
+            let info = trimmed[fence.len()..].trim();
+            let rust = info
+                .split(|c: char| c == ',' || c.is_whitespace())
+                .filter(|attribute| !attribute.is_empty())
+                .all(|attribute| {
+                    RUST_ATTRIBUTES.contains(&attribute) || attribute.starts_with("edition")
+                });
+            self.code_block = Some((fence.clone(), rust));
+            return Some(match rust {
+                true => format!("{indent}{fence}rust"),
+                false => line.to_string(),
+            });
+        }
+
+        // definitions pointing to items are replaced by the links that use them
+        if let Some((_, target)) = definition(line) {
+            if self.intra_doc_path(target).is_some() {
+                return None;
+            }
+        }
+
+        Some(self.rewrite_links(line))
+    }
+
+    /// Replaces the intra-doc links of a line with optional references
+    fn rewrite_links(&self, line: &str) -> String {
+        let mut result = String::with_capacity(line.len());
+        let mut rest = line;
+        while let Some(start) = rest.find(['[', '`']) {
+            result.push_str(&rest[..start]);
+            rest = &rest[start..];
+
+            // inline code is copied as is
+            if rest.starts_with('`') {
+                let ticks = rest.chars().take_while(|c| *c == '`').count();
+                let end = rest[ticks..]
+                    .find(&"`".repeat(ticks))
+                    .map_or(rest.len(), |end| ticks + end + ticks);
+                result.push_str(&rest[..end]);
+                rest = &rest[end..];
+                continue;
+            }
+
+            // escaped brackets are not links
+            if result.ends_with('\\') {
+                result.push('[');
+                rest = &rest[1..];
+                continue;
+            }
+
+            let Some(end) = closing_bracket(&rest[1..]) else {
+                result.push('[');
+                rest = &rest[1..];
+                continue;
+            };
+            let text = &rest[1..end + 1];
+            let after = &rest[end + 2..];
+
+            // the target of the link, and how long the link is
+            let (target, len) = if let Some(inline) = after.strip_prefix('(') {
+                match inline.find(')') {
+                    Some(close) => (self.intra_doc_path(&inline[..close]), end + 2 + close + 2),
+                    None => (None, end + 2),
+                }
+            } else if let Some(reference) = after.strip_prefix('[') {
+                match reference.find(']') {
+                    Some(close) => {
+                        let label = match &reference[..close] {
+                            "" => text,
+                            label => label,
+                        };
+                        (self.resolve_label(label), end + 2 + close + 2)
+                    }
+                    None => (None, end + 2),
+                }
+            } else {
+                (self.resolve_label(text), end + 2)
+            };
+
+            match target {
+                Some(path) if !after.starts_with(':') => {
+                    let id = path.rsplit("::").next().unwrap();
+                    result.push_str(&format!("{{ref?:{id}|{text}}}"));
+                    rest = &rest[len..];
+                }
+                _ => {
+                    result.push('[');
+                    rest = &rest[1..];
+                }
+            }
+        }
+        result.push_str(rest);
+        result
+    }
+
+    /// Returns the path of the item a link label points to, if any
+    fn resolve_label<'a>(&'a self, label: &'a str) -> Option<&'a str> {
+        match self.definitions.get(&label.to_lowercase()) {
+            Some(target) => self.intra_doc_path(target),
+            None => self.intra_doc_path(label),
+        }
+    }
+
+    /// Returns the path of the item an intra-doc link points to,
+    /// if the target can't be anything else (e.g. a relative link to a file)
+    fn intra_doc_path<'a>(&self, target: &'a str) -> Option<&'a str> {
+        let path = item_path(target)?;
+        let target = target.trim();
+        let first = path.split("::").next().unwrap_or_default();
+        let intra_doc = target.starts_with('`')
+            || target.contains('@')
+            || target.ends_with("()")
+            || target.ends_with('!')
+            || ["crate", "self", "super"].contains(&first)
+            || self.names.contains(first);
+        intra_doc.then_some(path)
+    }
+}
+
+/// Returns the name of the item declared on a line of Rust code, if there's one
+/// (e.g. `Config` for `pub(crate) struct Config {`)
+fn item_name(line: &str) -> Option<String> {
+    let words: Vec<_> = line
+        .split(|c: char| !(c.is_alphanumeric() || c == '_' || c == '!'))
+        .filter(|word| !word.is_empty())
+        .collect();
+
+    // the visibility and qualifiers of the item (e.g. `pub(super) const unsafe fn`)
+    let start = words
+        .iter()
+        .zip(words.iter().skip(1))
+        .position(|(word, next)| {
+            !(ITEM_QUALIFIERS.contains(word) || (*word == "const" && *next == "fn"))
+        })?;
+
+    match words[start..] {
+        [keyword, name, ..] if ITEM_KEYWORDS.contains(&keyword) => Some(name.to_string()),
+        _ => None,
+    }
+}
+
+/// Returns the offset of the bracket that closes a link text (ignoring the ones in inline code)
+fn closing_bracket(text: &str) -> Option<usize> {
+    let mut in_code = false;
+    for (offset, c) in text.char_indices() {
+        match c {
+            '`' => in_code = !in_code,
+            ']' if !in_code => return Some(offset),
+            '[' if !in_code => return None,
+            _ => (),
+        }
+    }
+    None
+}
+
+/// Splits a link reference definition (e.g. `[config]: crate::Config`) into its label and its target
+fn definition(line: &str) -> Option<(&str, &str)> {
+    let (label, target) = line.trim().strip_prefix('[')?.split_once("]:")?;
+    Some((label, target.trim()))
+}
+
+/// Returns the path of the item an intra-doc link points to, if the target is one
+/// (without its backticks, disambiguator, or parentheses)
+fn item_path(target: &str) -> Option<&str> {
+    let target = target.trim().trim_matches('`');
+    let target = match target.split_once('@') {
+        Some((kind, path)) if DISAMBIGUATORS.contains(&kind) => path,
+        Some(_) => return None,
+        None => target,
+    };
+    let target = target
+        .strip_suffix("()")
+        .or_else(|| target.strip_suffix('!'))
+        .unwrap_or(target);
+
+    let valid = target.split("::").all(|segment| {
+        segment
+            .chars()
+            .next()
+            .is_some_and(|c| c.is_alphabetic() || c == '_')
+            && segment.chars().all(|c| c.is_alphanumeric() || c == '_')
+    });
+    valid.then_some(target)
+}
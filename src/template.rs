@@ -0,0 +1,410 @@
+use miette::{NamedSource, Report, Result};
+use minijinja::{AutoEscape, Environment, UndefinedBehavior};
+use serde::Serialize;
+use serde_json::Value;
+use std::{
+    collections::HashSet,
+    error::Error,
+    fs,
+    ops::Range,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+};
+
+use crate::errors::SpecError;
+
+//~ The template is written with the [Jinja](https://jinja.palletsprojects.com/) syntax
+//~ (as implemented by [minijinja](https://docs.rs/minijinja/)):
+//~ placeholders are written `{{ sections.overview }}`,
+//~ and the template can use conditions (e.g. `{% if metadata.version %}`),
+//~ loops (e.g. `{% for name, content in sections|items %}`),
+//~ and include other templates (e.g. `{% include "chapters/intro.md" %}`),
+//~ whose paths are relative to the directory of the main template.
+//~
+//~ Before the template is rendered, every placeholder it contains
+//~ (or that the templates it includes contain) is checked:
+//~
+//~ 1. placeholders must refer to something that exists
+//~    (a section of the specification file, a named fragment of a section, a metadata field, etc.),
+//~    otherwise an error points to the placeholder and suggests the closest valid placeholder
+//~ 2. if they all do, sections that are never used in the template produce a warning
+//~
+
+/// Checks the template, and renders it with the context.
+/// Returns the rendered specification, the paths of all the templates used, and the sections that are not used.
+pub fn render(
+    template_path: &Path,
+    context: &impl Serialize,
+    sections: impl Iterator<Item = impl AsRef<str>>,
+) -> Result<(String, HashSet<PathBuf>, Vec<String>)> {
+    let template_dir = template_path
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_default();
+    let main = template_path
+        .file_name()
+        .map(|name| name.to_string_lossy().to_string())
+        .unwrap_or_default();
+
+    // templates are loaded from the directory of the main template,
+    // and we keep track of them to watch them
+    let loaded = Arc::new(Mutex::new(HashSet::new()));
+    let mut env = Environment::new();
+    env.set_keep_trailing_newline(true);
+    env.set_auto_escape_callback(|_| AutoEscape::None);
+    env.set_undefined_behavior(UndefinedBehavior::SemiStrict);
+    env.set_loader({
+        let template_dir = template_dir.clone();
+        let loaded = loaded.clone();
+        move |name| {
+            let path = template_dir.join(name);
+            match fs::read_to_string(&path) {
+                Ok(source) => {
+                    loaded.lock().unwrap().insert(path);
+                    Ok(Some(source))
+                }
+                Err(_) => Ok(None),
+            }
+        }
+    });
+
+    let template = env
+        .get_template(&main)
+        .map_err(|e| template_error(e, &template_dir))?;
+
+    let unused_sections = check(&env, &template_dir, &main, context, sections)?;
+
+    let rendered = template
+        .render(context)
+        .map_err(|e| template_error(e, &template_dir))?;
+
+    let loaded = loaded.lock().unwrap().clone();
+    Ok((rendered, loaded, unused_sections))
+}
+
+/// Checks that all the placeholders of the template (and of the templates it includes)
+/// refer to a value of the context, and returns the sections that are not used.
+fn check(
+    env: &Environment,
+    template_dir: &Path,
+    main: &str,
+    context: &impl Serialize,
+    sections: impl Iterator<Item = impl AsRef<str>>,
+) -> Result<Vec<String>> {
+    let context = serde_json::to_value(context).expect("couldn't serialize the template context");
+    let globals: HashSet<_> = env.globals().map(|(name, _)| name.to_string()).collect();
+
+    let mut problems = vec![];
+    let mut used_sections = HashSet::new();
+    let mut all_sections_used = false;
+
+    let mut to_check = vec![main.to_string()];
+    let mut checked = HashSet::new();
+    while let Some(name) = to_check.pop() {
+        if !checked.insert(name.clone()) {
+            continue;
+        }
+
+        let template = env
+            .get_template(&name)
+            .map_err(|e| template_error(e, template_dir))?;
+        let source = template.source();
+        let src = || {
+            NamedSource::new(
+                template_dir.join(&name).display().to_string(),
+                source.to_string(),
+            )
+        };
+        to_check.extend(includes(source));
+
+        for (offset, placeholder) in legacy_placeholders(source) {
+            problems.push(SpecError::LegacyPlaceholder {
+                placeholder: placeholder.to_string(),
+                src: src(),
+                bad_bit: (offset, placeholder.len() + 2),
+            });
+        }
+
+        // the variables are checked in the order they appear in
+        let mut variables: Vec<_> = template
+            .undeclared_variables(true)
+            .into_iter()
+            .map(|path| (occurrences(source, &path), path))
+            .collect();
+        variables.sort();
+        for (offsets, path) in variables {
+            let mut steps = path.split('.');
+            let root = steps.next().unwrap_or_default();
+            match (root, steps.next()) {
+                ("sections" | "fragments", Some(section)) => {
+                    used_sections.insert(section.to_string());
+                }
+                ("sections" | "fragments", None) => all_sections_used = true,
+                _ => (),
+            }
+
+            // variables that are not part of the context
+            // might be defined by the template including this one
+            let in_context = context.get(root).is_some();
+            if globals.contains(root) || (!in_context && name != main) {
+                continue;
+            }
+
+            if let Err(help) = lookup(&context, &path) {
+                if offsets.is_empty() {
+                    // the placeholder is only tested with `is defined`
+                    continue;
+                }
+
+                problems.push(SpecError::UnknownPlaceholder {
+                    placeholder: path.clone(),
+                    help,
+                    src: src(),
+                    bad_bit: (offsets.first().copied().unwrap_or_default(), path.len()),
+                });
+            }
+        }
+    }
+
+    match problems.len() {
+        0 => Ok(sections
+            .map(|section| section.as_ref().to_string())
+            .filter(|section| !all_sections_used && !used_sections.contains(section))
+            .collect()),
+        1 => Err(problems.remove(0).into()),
+        _ => Err(SpecError::BadTemplate {
+            path: template_dir.join(main),
+            problems,
+        }
+        .into()),
+    }
+}
+
+/// Converts an error of the template engine to a diagnostic pointing to the template
+/// (or to the included template the error comes from)
+fn template_error(error: minijinja::Error, template_dir: &Path) -> Report {
+    let mut error = &error;
+    while let Some(inner) = error
+        .source()
+        .and_then(|e| e.downcast_ref::<minijinja::Error>())
+    {
+        error = inner;
+    }
+
+    let message = error
+        .detail()
+        .map(str::to_string)
+        .unwrap_or_else(|| error.kind().to_string());
+
+    let path = error.name().map(|name| template_dir.join(name));
+    let source = path.as_ref().and_then(|path| fs::read_to_string(path).ok());
+    match (path, source) {
+        (Some(path), Some(source)) => {
+            let bad_bit = match (error.range(), error.line()) {
+                (Some(range), _) => (range.start, range.len()),
+                (None, Some(line)) => {
+                    let offset: usize = source
+                        .split_inclusive('\n')
+                        .take(line.saturating_sub(1))
+                        .map(str::len)
+                        .sum();
+                    let len = source[offset..].lines().next().map_or(0, str::len);
+                    (offset, len)
+                }
+                (None, None) => (0, 0),
+            };
+            SpecError::TemplateError {
+                message,
+                src: NamedSource::new(path.display().to_string(), source),
+                bad_bit,
+            }
+            .into()
+        }
+        _ => SpecError::CantRenderTemplate(error.to_string()).into(),
+    }
+}
+
+/// Makes sure that a path refers to a value of the context,
+/// or returns a hint about what the path could have been.
+fn lookup(context: &Value, path: &str) -> Result<(), String> {
+    let steps: Vec<_> = path.split('.').collect();
+    let mut value = context;
+    for (i, step) in steps.iter().enumerate() {
+        let next = match value {
+            Value::Object(fields) => fields.get(*step),
+            Value::Array(values) => step.parse::<usize>().ok().and_then(|i| values.get(i)),
+            _ => None,
+        };
+
+        value = match next {
+            Some(next) => next,
+            None => {
+                let parent = if i == 0 {
+                    "the template".to_string()
+                } else {
+                    format!("`{}`", steps[..i].join("."))
+                };
+
+                let candidates = match value {
+                    Value::Object(fields) => fields.keys().map(String::as_str).collect(),
+                    _ => vec![],
+                };
+
+                return Err(match did_you_mean(step, &candidates) {
+                    Some(candidate) => {
+                        let mut suggestion = steps.clone();
+                        suggestion[i] = candidate;
+                        format!("did you mean `{}`?", suggestion.join("."))
+                    }
+                    None if candidates.is_empty() => format!("{parent} has no fields"),
+                    None => format!(
+                        "{parent} only has {}",
+                        candidates
+                            .iter()
+                            .map(|c| format!("`{c}`"))
+                            .collect::<Vec<_>>()
+                            .join(", ")
+                    ),
+                });
+            }
+        };
+    }
+
+    Ok(())
+}
+
+/// Returns the candidate that is the closest to `name`, if any is close enough
+pub fn did_you_mean<'a>(name: &str, candidates: &[&'a str]) -> Option<&'a str> {
+    candidates
+        .iter()
+        .map(|candidate| (strsim::jaro_winkler(name, candidate), *candidate))
+        .filter(|(confidence, _)| *confidence > 0.8)
+        .max_by(|(a, _), (b, _)| a.total_cmp(b))
+        .map(|(_, candidate)| candidate)
+}
+
+/// Returns the offsets where a variable is used in a template,
+/// except when it's only tested with `is defined` or `is undefined`.
+fn occurrences(source: &str, path: &str) -> Vec<usize> {
+    let is_ident = |c: char| c.is_alphanumeric() || c == '_' || c == '.';
+    source
+        .match_indices(path)
+        .map(|(offset, _)| offset)
+        .filter(|offset| {
+            let before = source[..*offset].chars().next_back();
+            let after = &source[offset + path.len()..];
+            let mut words = after.split_whitespace();
+            let tested = words.next() == Some("is")
+                && words
+                    .next()
+                    .is_some_and(|test| matches!(test, "defined" | "undefined" | "not"));
+
+            !before.is_some_and(is_ident) && !after.starts_with(is_ident) && !tested
+        })
+        .collect()
+}
+
+/// Returns the names of the templates included (or imported) by a template
+fn includes(source: &str) -> Vec<String> {
+    let mut includes = vec![];
+    for block in source.split("{%").skip(1) {
+        let block = block.split("%}").next().unwrap_or_default();
+        let block = block.trim_matches(|c: char| c == '-' || c == '+' || c.is_whitespace());
+        let keyword = block.split_whitespace().next().unwrap_or_default();
+        if !matches!(keyword, "include" | "import" | "from" | "extends") {
+            continue;
+        }
+
+        // every string literal of the block is a template name
+        let mut rest = block;
+        while let Some(start) = rest.find(['"', '\'']) {
+            let quote = &rest[start..start + 1];
+            let literal = &rest[start + 1..];
+            match literal.find(quote) {
+                Some(end) => {
+                    includes.push(literal[..end].to_string());
+                    rest = &literal[end + 1..];
+                }
+                None => break,
+            }
+        }
+    }
+    includes
+}
+
+/// Returns the placeholders written with the syntax of previous versions of cargo-spec
+/// (e.g. `{sections.overview}` instead of `{{ sections.overview }}`), along with their offsets.
+/// Code blocks and inline code are left out, as they can contain such braces.
+fn legacy_placeholders(source: &str) -> Vec<(usize, &str)> {
+    let mut placeholders = vec![];
+    let mut fence: Option<String> = None;
+    let mut line_start = 0;
+    for line in source.split_inclusive('\n') {
+        let line_offset = line_start;
+        line_start += line.len();
+
+        let trimmed = line.trim_start();
+        match &fence {
+            Some(open) => {
+                if trimmed.trim_end().starts_with(open.as_str())
+                    && trimmed.trim_end().chars().all(|c| open.starts_with(c))
+                {
+                    fence = None;
+                }
+                continue;
+            }
+            None if trimmed.starts_with("```") || trimmed.starts_with("~~~") => {
+                let marker = trimmed.chars().next().unwrap_or('`');
+                fence = Some(trimmed.chars().take_while(|c| *c == marker).collect());
+                continue;
+            }
+            None => (),
+        }
+
+        let code = inline_code(line);
+        for (offset, _) in line.match_indices('{') {
+            if code.iter().any(|code| code.contains(&offset)) {
+                continue;
+            }
+            let before = line[..offset].chars().next_back();
+            let rest = &line[offset + 1..];
+            if matches!(before, Some('{' | '\\')) || rest.starts_with(['{', '%', '#']) {
+                continue;
+            }
+
+            let Some(end) = rest.find('}') else {
+                continue;
+            };
+            let placeholder = &rest[..end];
+            let root = placeholder.split('.').next().unwrap_or_default();
+            if matches!(root, "sections" | "fragments" | "metadata" | "config")
+                && placeholder
+                    .chars()
+                    .all(|c| c.is_alphanumeric() || c == '_' || c == '.')
+            {
+                placeholders.push((line_offset + offset, placeholder));
+            }
+        }
+    }
+    placeholders
+}
+
+/// Returns where the inline code of a line is (backticks included)
+fn inline_code(line: &str) -> Vec<Range<usize>> {
+    let mut code = vec![];
+    let mut start = 0;
+    while let Some(offset) = line[start..].find('`') {
+        let open = start + offset;
+        let ticks = line[open..].chars().take_while(|c| *c == '`').count();
+        let delimiter = "`".repeat(ticks);
+        match line[open + ticks..].find(&delimiter) {
+            Some(end) => {
+                let close = open + ticks + end + ticks;
+                code.push(open..close);
+                start = close;
+            }
+            None => start = open + ticks,
+        }
+    }
+    code
+}
@@ -1,31 +1,127 @@
+use indexmap::IndexMap;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::fs::File;
 use std::io::Read;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
-use miette::{IntoDiagnostic, Result, WrapErr};
+use miette::{IntoDiagnostic, NamedSource, Report, Result, WrapErr};
+use toml::Spanned;
+
+use crate::{
+    build::OutputFormat,
+    errors::{BadEntry, SpecError},
+    features::Edition,
+};
 
 //~ spec:startcode
 /// A specification file contains a specification, as well as sections of (title, text)
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Specification {
     /// information about a specification
     pub metadata: Metadata,
     /// configuration of the specification
     pub config: Config,
-    /// files to use for the specification's content
-    pub sections: HashMap<String, String>,
+    /// files to use for the specification's content (in order)
+    pub sections: IndexMap<String, Section>,
+    /// how to find spec comments in files, indexed by file extension
+    #[serde(default, skip_serializing_if = "HashMap::is_empty")]
+    pub languages: HashMap<String, Language>,
+    /// the features enabled by each profile (e.g. `internal = ["internal", "drafts"]`)
+    #[serde(default, skip_serializing_if = "IndexMap::is_empty")]
+    pub profiles: IndexMap<String, Vec<String>>,
+    /// the outputs `cargo spec build` produces, if none is given on the command line
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub outputs: Vec<Target>,
+}
+
+/// An output of the specification (e.g. `{ format = "respec", path = "spec.html", profile = "public" }`)
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq, Eq)]
+pub struct Target {
+    /// format of the output
+    pub format: OutputFormat,
+    /// where the output is written, relative to the specification file (defaults to the path of the format)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub path: Option<PathBuf>,
+    /// profile to build the output with
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    /// other features to enable
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub features: Vec<String>,
+    /// overrides `numbering` in the config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub numbering: Option<bool>,
+    /// overrides `toc` in the config
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub toc: Option<bool>,
 }
 
+/// A section is made of one or several entries,
+/// each one being a file, a glob pattern (e.g. `src/**/*.rs`), or a Rust item (e.g. `item:crate::Spec`)
 #[derive(Serialize, Deserialize, Debug)]
+#[serde(untagged)]
+pub enum Section {
+    Single(String),
+    Many(Vec<String>),
+    /// a section written as a table, to change what is extracted from its files
+    Table(SectionTable),
+}
+
+/// A section with options (e.g. `api = { files = ["src/lib.rs"], mode = "docs" }`)
+#[derive(Serialize, Deserialize, Debug)]
+pub struct SectionTable {
+    /// the entries of the section
+    pub files: Vec<String>,
+    /// what is extracted from the files
+    #[serde(default)]
+    pub mode: SectionMode,
+    /// in `docs` mode, the Rust items (e.g. `crate::api::Client`) whose doc comments are extracted,
+    /// if only some of them should be
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub items: Vec<String>,
+}
+
+/// What is extracted from the files of a section
+#[derive(Serialize, Deserialize, Debug, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum SectionMode {
+    /// spec comments only
+    #[default]
+    Spec,
+    /// spec comments, as well as the doc comments of Rust files (`///` and `//!`)
+    Docs,
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
 pub struct Config {
     /// main template file
     pub template: String,
+    /// number the headers (e.g. `1.2 Handshake message`)
+    #[serde(default)]
+    pub numbering: bool,
+    /// insert a table of contents before the first section
+    #[serde(default)]
+    pub toc: bool,
+    /// files (or glob patterns) of the tests that cover requirements, if they are not part of a section
+    #[serde(default)]
+    pub tests: Vec<String>,
+}
+
+/// How spec comments are written in a language.
+/// Spec comments are comments that start with a `~` right after the delimiter (e.g. `//~` or `/*~`).
+#[derive(Serialize, Deserialize, Debug, Clone, Default)]
+pub struct Language {
+    /// delimiter of single-line comments (e.g. `//`)
+    pub line_comment: Option<String>,
+    /// start and end delimiters of block comments (e.g. `["/*", "*/"]`)
+    pub block_comment: Option<(String, String)>,
+    /// name of the language in code fences (e.g. `rust`)
+    pub fence: Option<String>,
 }
 
 /// Metadata about a specification
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Default)]
 pub struct Metadata {
     /// Name of the specification
     pub name: String,
@@ -38,8 +134,196 @@ pub struct Metadata {
 }
 //~ spec:endcode
 
+impl Target {
+    /// An output in a format, at the path of the format
+    pub fn new(format: OutputFormat) -> Self {
+        Self {
+            format,
+            path: None,
+            profile: None,
+            features: vec![],
+            numbering: None,
+            toc: None,
+        }
+    }
+
+    /// The outputs for several editions of the specification, in a format, at `path` (or the path of the format).
+    /// If there are several editions, the name of their profile is added to the path.
+    pub fn editions(
+        format: OutputFormat,
+        path: Option<PathBuf>,
+        editions: &[Edition],
+    ) -> Vec<Self> {
+        let path = path.unwrap_or_else(|| format.default_output());
+        editions
+            .iter()
+            .map(|edition| Self {
+                path: Some(match editions.len() {
+                    1 => path.clone(),
+                    _ => edition.output_path(&path),
+                }),
+                profile: edition.profile.clone(),
+                features: edition.features.clone(),
+                ..Self::new(format)
+            })
+            .collect()
+    }
+
+    /// The edition of the specification the output is built from
+    pub fn edition(&self) -> Edition {
+        Edition {
+            profile: self.profile.clone(),
+            features: self.features.clone(),
+        }
+    }
+
+    /// Where the output is written
+    pub fn path(&self) -> PathBuf {
+        self.path
+            .clone()
+            .unwrap_or_else(|| self.format.default_output())
+    }
+}
+
+impl Section {
+    /// The entries of the section, in order
+    pub fn entries(&self) -> &[String] {
+        match self {
+            Section::Single(entry) => std::slice::from_ref(entry),
+            Section::Many(entries) => entries,
+            Section::Table(table) => &table.files,
+        }
+    }
+
+    /// What is extracted from the files of the section
+    pub fn mode(&self) -> SectionMode {
+        match self {
+            Section::Table(table) => table.mode,
+            _ => SectionMode::Spec,
+        }
+    }
+
+    /// The Rust items whose doc comments are extracted (all of them, if there's none)
+    pub fn items(&self) -> &[String] {
+        match self {
+            Section::Table(table) => &table.items,
+            _ => &[],
+        }
+    }
+}
+
+/// Where the entries of a specification file are, to point to them in errors
+pub(crate) struct Manifest {
+    path: PathBuf,
+    source: String,
+    /// the spans of the values, indexed by their path (e.g. `sections.overview` or `config.template`)
+    spans: HashMap<String, (usize, usize)>,
+}
+
+/// The values of a specification file that can point to files, along with their spans
+#[derive(Deserialize)]
+struct ManifestSpans {
+    config: ConfigSpans,
+    #[serde(default)]
+    sections: HashMap<String, Spanned<toml::Value>>,
+    #[serde(default)]
+    outputs: Vec<TargetSpans>,
+}
+
+#[derive(Deserialize)]
+struct TargetSpans {
+    profile: Option<Spanned<toml::Value>>,
+}
+
+#[derive(Deserialize)]
+struct ConfigSpans {
+    template: Spanned<toml::Value>,
+    tests: Option<Spanned<toml::Value>>,
+}
+
+impl Manifest {
+    fn new(path: &Path, source: String) -> Self {
+        let mut spans = HashMap::new();
+        if let Ok(manifest) = toml::from_str::<ManifestSpans>(&source) {
+            spans.insert(
+                "config.template".to_string(),
+                manifest.config.template.span(),
+            );
+            if let Some(tests) = manifest.config.tests {
+                spans.insert("config.tests".to_string(), tests.span());
+            }
+            for (name, section) in manifest.sections {
+                spans.insert(format!("sections.{name}"), section.span());
+            }
+            for (index, target) in manifest.outputs.iter().enumerate() {
+                if let Some(profile) = &target.profile {
+                    spans.insert(format!("outputs.{index}.profile"), profile.span());
+                }
+            }
+        }
+
+        // the values of tables written with a header (e.g. `[sections.api]`) have no span,
+        // so they span from their header to the next one
+        for (key, span) in &mut spans {
+            if span.0 != span.1 {
+                continue;
+            }
+            if let Some(start) = source.find(&format!("[{key}]")) {
+                let end = source[start + 1..]
+                    .find("\n[")
+                    .map_or(source.len(), |end| start + 1 + end);
+                *span = (start, end);
+            }
+        }
+
+        Self {
+            path: path.to_path_buf(),
+            source,
+            spans,
+        }
+    }
+
+    /// The span of the value at `key` (e.g. `sections.overview`), as an offset and a length
+    pub fn span(&self, key: &str) -> Option<(usize, usize)> {
+        self.spans
+            .get(key)
+            .map(|&(start, end)| (start, end - start))
+    }
+
+    /// The specification file, to point to it in errors
+    pub fn source(&self) -> NamedSource {
+        NamedSource::new(self.path.display().to_string(), self.source.clone())
+    }
+
+    /// Points to the entry (at `key`) that caused an error, along with the error itself
+    pub fn entry_error(&self, key: &str, entry: &str, error: Report) -> Report {
+        let Some(&(start, end)) = self.spans.get(key) else {
+            return error;
+        };
+
+        // the entry itself, if the value is a list
+        let bad_bit = match self.source[start..end].find(&format!("\"{entry}\"")) {
+            Some(offset) => (start + offset, entry.len() + 2),
+            None => (start, end - start),
+        };
+        BadEntry {
+            key: key.to_string(),
+            entry: entry.to_string(),
+            src: self.source(),
+            bad_bit,
+            problems: vec![error],
+        }
+        .into()
+    }
+}
+
 /// Parse a `Specification.toml` file into a [Specification] struct.
 pub fn parse_toml_spec(spec_file: &Path) -> Result<Specification> {
+    parse_manifest(spec_file).map(|(specification, _)| specification)
+}
+
+/// Parse a `Specification.toml` file, and keep track of where its entries are
+pub(crate) fn parse_manifest(spec_file: &Path) -> Result<(Specification, Manifest)> {
     let mut file = File::open(spec_file).into_diagnostic().wrap_err_with(|| format!("cannot open the specification file {}, make sure you pass a specification toml file via --specification-path", spec_file.display()))?;
 
     let mut content = String::new();
@@ -52,5 +336,27 @@ pub fn parse_toml_spec(spec_file: &Path) -> Result<Specification> {
             )
         })?;
 
-    toml::from_str(&content).into_diagnostic()
+    let specification = toml::from_str(&content).map_err(|e| {
+        // the error points to the rest of the line where the problem is
+        let offset = e
+            .line_col()
+            .map(|(line, column)| {
+                content
+                    .split_inclusive('\n')
+                    .take(line)
+                    .map(str::len)
+                    .sum::<usize>()
+                    + column
+            })
+            .unwrap_or_default()
+            .min(content.len());
+        let len = content[offset..].lines().next().map_or(0, str::len);
+        SpecError::BadManifest {
+            message: e.to_string(),
+            src: NamedSource::new(spec_file.display().to_string(), content.clone()),
+            bad_bit: (offset, len),
+        }
+    })?;
+
+    Ok((specification, Manifest::new(spec_file, content)))
 }
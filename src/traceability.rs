@@ -0,0 +1,155 @@
+use miette::{NamedSource, Result};
+use serde::Serialize;
+use std::{fmt::Write as FmtWrite, fs, path::PathBuf};
+
+use crate::{errors::SpecError, requirements::Requirement, template::did_you_mean};
+
+//~ Tests are linked to the [requirements](#requirements) they cover with `spec:tests(ID)`
+//~ (or `spec:tests(ID-1, ID-2)` for several requirements), right before the test function:
+//~
+//~ 1. the name of the test is taken from the first line that follows the instruction
+//~    and that isn't empty, a comment, an attribute (`#[...]`), or a decorator (`@...`):
+//~    it's the identifier right before the first parenthesis (e.g. `rejects_future_blocks` in `fn rejects_future_blocks() {`)
+//~ 2. the instruction is found in the files of the sections,
+//~    as well as in the files listed by `tests` in the `[config]` of the specification file
+//~    (like sections, a list of files and glob patterns), which are not part of the specification
+//~ 3. a test can only cover requirements that are registered in the specification
+//~ 4. the tests covering a requirement are available in the template as the `tests` of the requirement,
+//~    each one with a `name`, a `file`, and a `line`
+//~ 5. `cargo spec trace` writes the traceability matrix, as a markdown table and as JSON,
+//~    and can fail if a requirement isn't covered by any test
+//~
+
+/// A test covering requirements (`spec:tests(ID)`)
+#[derive(Serialize, Debug, Clone)]
+pub struct Test {
+    pub name: String,
+    /// the file the test is in
+    pub file: PathBuf,
+    /// the line of the `spec:tests` instruction (starting at 1)
+    pub line: usize,
+}
+
+/// A test along with the IDs of the requirements it covers, as found in a file
+#[derive(Debug, Clone)]
+pub struct TestLink {
+    pub test: Test,
+    pub requirements: Vec<String>,
+    /// the offset of the `spec:tests` instruction in the file
+    pub offset: usize,
+}
+
+/// Which tests cover each requirement
+#[derive(Serialize, Debug)]
+pub struct Matrix<'a> {
+    pub requirements: &'a [Requirement],
+    /// the IDs of the requirements that no test covers
+    pub uncovered: Vec<&'a str>,
+}
+
+impl<'a> Matrix<'a> {
+    pub fn new(requirements: &'a [Requirement]) -> Self {
+        let uncovered = requirements
+            .iter()
+            .filter(|requirement| requirement.tests.is_empty())
+            .map(|requirement| requirement.id.as_str())
+            .collect();
+        Self {
+            requirements,
+            uncovered,
+        }
+    }
+
+    /// The matrix as a markdown table, followed by a summary
+    pub fn to_markdown(&self) -> String {
+        let mut markdown = String::new();
+        writeln!(markdown, "| Requirement | Source | Tests |").unwrap();
+        writeln!(markdown, "|-------------|--------|-------|").unwrap();
+        for requirement in self.requirements {
+            let tests = if requirement.tests.is_empty() {
+                "**none**".to_string()
+            } else {
+                requirement
+                    .tests
+                    .iter()
+                    .map(|test| format!("`{}` ({}:{})", test.name, test.file.display(), test.line))
+                    .collect::<Vec<_>>()
+                    .join("<br>")
+            };
+            writeln!(
+                markdown,
+                "| **{}**: {} | {}:{} | {} |",
+                requirement.id,
+                requirement.text.replace('|', "\\|"),
+                requirement.file.display(),
+                requirement.line,
+                tests
+            )
+            .unwrap();
+        }
+
+        let covered = self.requirements.len() - self.uncovered.len();
+        writeln!(
+            markdown,
+            "\n{covered} of {} requirements are covered by tests.",
+            self.requirements.len()
+        )
+        .unwrap();
+        markdown
+    }
+
+    /// The matrix as JSON
+    pub fn to_json(&self) -> String {
+        serde_json::to_string_pretty(self).expect("couldn't serialize the traceability matrix")
+            + "\n"
+    }
+}
+
+/// Adds the tests to the requirements they cover.
+/// Fails if a test covers a requirement that doesn't exist.
+pub fn link(requirements: &mut [Requirement], tests: Vec<TestLink>) -> Result<()> {
+    for link in tests {
+        for id in &link.requirements {
+            let Some(requirement) = requirements.iter_mut().find(|r| &r.id == id) else {
+                let ids: Vec<_> = requirements.iter().map(|r| r.id.as_str()).collect();
+                let help = match did_you_mean(id, &ids) {
+                    Some(candidate) => format!("did you mean `{candidate}`?"),
+                    None => "requirements are registered with `spec:req(ID)`".to_string(),
+                };
+                let source = fs::read_to_string(&link.test.file).unwrap_or_default();
+                return Err(SpecError::UnknownRequirement {
+                    test: link.test.name.clone(),
+                    id: id.clone(),
+                    help,
+                    src: NamedSource::new(link.test.file.display().to_string(), source),
+                    bad_bit: (link.offset, "tests".len()),
+                }
+                .into());
+            };
+            requirement.tests.push(link.test.clone());
+        }
+    }
+    Ok(())
+}
+
+/// Returns the name of the test function that follows a `spec:tests` instruction
+pub fn test_name<'a>(
+    mut lines: impl Iterator<Item = &'a str>,
+    line_comment: Option<&str>,
+) -> Option<String> {
+    let line = lines.find(|line| {
+        let line = line.trim();
+        !line.is_empty()
+            && !line.starts_with("#[")
+            && !line.starts_with('@')
+            && !line_comment.is_some_and(|comment| line.starts_with(comment))
+    })?;
+
+    // the identifier right before the parenthesis (ignoring generic parameters)
+    let signature = line.split('(').next()?;
+    let signature = signature.split('<').next()?;
+    let name = signature
+        .rsplit(|c: char| !(c.is_alphanumeric() || c == '_'))
+        .find(|word| !word.is_empty())?;
+    line.contains('(').then(|| name.to_string())
+}
@@ -0,0 +1,39 @@
+mod common;
+
+use cargo_spec::build::{build_targets, check};
+use common::{manifest_path, spec_with, write, MANIFEST};
+use std::fs;
+
+#[test]
+fn checks_that_the_specification_is_up_to_date() {
+    // the output is written next to the specification file
+    let manifest = format!("{MANIFEST}\n[[outputs]]\nformat = \"markdown\"\n");
+    let dir = spec_with(
+        &manifest,
+        "{{ sections.code }}",
+        &[("lib.rs", "//~ Some spec\n")],
+    );
+    let outdated = format!(
+        "The specification is out of date: {}",
+        dir.path().join("specification.md").display()
+    );
+
+    // nothing was built yet
+    let error = check(manifest_path(&dir), &[]).unwrap_err();
+    assert_eq!(error.to_string(), outdated);
+
+    build_targets(manifest_path(&dir), &[]).unwrap();
+    check(manifest_path(&dir), &[]).unwrap();
+
+    // the code changed, but not the specification
+    write(&dir, "lib.rs", "//~ Some other spec\n");
+    let error = check(manifest_path(&dir), &[]).unwrap_err();
+    assert_eq!(error.to_string(), outdated);
+
+    build_targets(manifest_path(&dir), &[]).unwrap();
+    check(manifest_path(&dir), &[]).unwrap();
+    assert_eq!(
+        fs::read_to_string(dir.path().join("specification.md")).unwrap(),
+        "Some other spec\n"
+    );
+}
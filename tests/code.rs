@@ -0,0 +1,143 @@
+mod common;
+
+use cargo_spec::build::OutputFormat;
+use common::{render, render_text, spec_with, write, MANIFEST};
+
+#[test]
+fn dedents_code_and_keeps_options() {
+    let dir = spec_with(
+        MANIFEST,
+        "{{ sections.code }}",
+        &[(
+            "lib.rs",
+            "impl Handshake {
+    //~ spec:startcode(linenos, highlight=2, highlight=4-5)
+    fn send(&self) {
+        self.write();
+
+        self.flush();
+    }
+    //~ spec:endcode
+    //~ spec:startcode(lang=text)
+        nested
+    //~ spec:endcode
+}
+",
+        )],
+    );
+
+    assert_eq!(
+        render_text(&dir, OutputFormat::Markdown),
+        "```rust linenos highlight=2 highlight=4-5
+fn send(&self) {
+    self.write();
+
+    self.flush();
+}
+```
+```text
+nested
+```
+"
+    );
+
+    let html = render_text(&dir, OutputFormat::Html);
+    assert!(html.contains(
+        "<pre lang=\"rust\" class=\"linenos\"><code><span class=\"line\" data-line=\"1\">fn send(&amp;self) {</span>
+<span class=\"line highlighted\" data-line=\"2\">    self.write();</span>
+<span class=\"line\" data-line=\"3\"></span>
+<span class=\"line highlighted\" data-line=\"4\">    self.flush();</span>
+<span class=\"line highlighted\" data-line=\"5\">}</span>
+</code></pre>"
+    ));
+    assert!(html.contains("<pre lang=\"text\"><code>nested\n</code></pre>"));
+}
+
+#[test]
+fn hides_lines() {
+    let manifest = MANIFEST.replace("code = \"lib.rs\"", "code = [\"lib.rs\", \"lib.ml\"]");
+    let dir = spec_with(
+        &manifest,
+        "{{ sections.code }}",
+        &[
+            (
+                "lib.rs",
+                "//~ spec:startcode
+fn handshake() {
+    let message = receive();
+    //~ spec:hide
+    log(&message);
+
+    check(&message);
+    //~ spec:show
+    reply(message);
+}
+//~ spec:endcode
+",
+            ),
+            (
+                "lib.ml",
+                "(*~ spec:startcode *)\nlet x = 1\n(*~ spec:hide *)\nlet y = 2\n(*~ spec:endcode *)\n",
+            ),
+        ],
+    );
+
+    assert_eq!(
+        render_text(&dir, OutputFormat::Markdown),
+        "```rust
+fn handshake() {
+    let message = receive();
+    // ...
+    reply(message);
+}
+```
+
+```ocaml
+let x = 1
+(* ... *)
+```
+"
+    );
+
+    write(&dir, "lib.rs", "//~ spec:show\n");
+    let error = render(&dir, OutputFormat::Markdown).unwrap_err();
+    assert!(format!("{error:?}").contains("spec:startcode"), "{error:?}");
+}
+
+#[test]
+fn dedents_mixed_indentation() {
+    let dir = spec_with(
+        MANIFEST,
+        "{{ sections.code }}",
+        &[(
+            "lib.rs",
+            "mod handshake {
+\t//~ spec:startcode
+\t\tfn tabs() {}
+\t    fn spaces() {}
+
+\t//~ spec:endcode
+    //~ spec:startcode
+    fn spaces() {}
+\tfn tab() {}
+    //~ spec:endcode
+}
+",
+        )],
+    );
+
+    // only the indentation the lines have in common is removed (a tab and spaces are different)
+    assert_eq!(
+        render_text(&dir, OutputFormat::Markdown),
+        "```rust
+\tfn tabs() {}
+    fn spaces() {}
+
+```
+```rust
+    fn spaces() {}
+\tfn tab() {}
+```
+"
+    );
+}
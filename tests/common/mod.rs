@@ -0,0 +1,53 @@
+//! Helpers shared by the integration tests.
+#![allow(dead_code)]
+
+use cargo_spec::build::{render_file, Output, OutputFormat};
+use std::{fs, path::PathBuf};
+use tempfile::TempDir;
+
+/// A specification file with a single section, `code`, made of `lib.rs`
+pub const MANIFEST: &str = "[metadata]
+name = \"test\"
+authors = []
+
+[config]
+template = \"template.md\"
+
+[sections]
+code = \"lib.rs\"
+";
+
+/// Creates a specification in a temporary directory: its specification file, its template (`template.md`),
+/// and other files, as (path, content)
+pub fn spec_with(manifest: &str, template: &str, files: &[(&str, &str)]) -> TempDir {
+    let dir = tempfile::tempdir().unwrap();
+    write(&dir, "Specification.toml", manifest);
+    write(&dir, "template.md", template);
+    for (path, content) in files {
+        write(&dir, path, content);
+    }
+    dir
+}
+
+/// Writes a file in the directory of a specification, creating its parent directories if needed
+pub fn write(dir: &TempDir, path: &str, content: &str) {
+    let path = dir.path().join(path);
+    fs::create_dir_all(path.parent().unwrap()).unwrap();
+    fs::write(path, content).unwrap();
+}
+
+/// The path to the specification file of a specification
+pub fn manifest_path(dir: &TempDir) -> PathBuf {
+    dir.path().join("Specification.toml")
+}
+
+/// Builds a specification in memory
+pub fn render(dir: &TempDir, format: OutputFormat) -> miette::Result<Output> {
+    render_file(&manifest_path(dir), None, format)
+}
+
+/// Builds a specification in memory, and returns its (only) file as text
+pub fn render_text(dir: &TempDir, format: OutputFormat) -> String {
+    let output = render(dir, format).unwrap();
+    output.files[0].as_str().unwrap().to_string()
+}
@@ -0,0 +1,147 @@
+//! Snapshots of the errors, as they are rendered by the CLI.
+//! Run with `UPDATE_SNAPSHOTS=1` to update the snapshots in `tests/snapshots`.
+
+mod common;
+
+use cargo_spec::build::render_targets;
+use common::manifest_path;
+use miette::{Diagnostic, GraphicalReportHandler, GraphicalTheme};
+use std::{env, fs, path::Path};
+
+/// A specification file with a section made of two files, to point to one of them
+fn manifest() -> String {
+    common::MANIFEST.replace("code = \"lib.rs\"", "code = [\"lib.rs\", \"other.rs\"]")
+}
+
+fn spec_with(manifest: &str, source: &str) -> tempfile::TempDir {
+    common::spec_with(
+        manifest,
+        "{{ sections.code }}\n",
+        &[("lib.rs", source), ("other.rs", "//~ other\n")],
+    )
+}
+
+/// Renders the error of the specification without colors, with the temporary directory hidden
+fn render_error(dir: &tempfile::TempDir) -> String {
+    let report = render_targets(&manifest_path(dir), &[]).unwrap_err();
+    render_report(dir, report.as_ref())
+}
+
+/// Renders a report without colors, with the temporary directory hidden
+fn render_report(dir: &tempfile::TempDir, report: &dyn Diagnostic) -> String {
+    let mut rendered = String::new();
+    GraphicalReportHandler::new_themed(GraphicalTheme::unicode_nocolor())
+        .with_width(100)
+        .render_report(&mut rendered, report)
+        .unwrap();
+
+    let canonical = dir.path().canonicalize().unwrap();
+    rendered
+        .replace(&canonical.display().to_string(), "[dir]")
+        .replace(&dir.path().display().to_string(), "[dir]")
+}
+
+fn assert_snapshot(name: &str, actual: &str) {
+    let path = Path::new(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/snapshots")
+        .join(format!("{name}.txt"));
+    if env::var_os("UPDATE_SNAPSHOTS").is_some() {
+        fs::create_dir_all(path.parent().unwrap()).unwrap();
+        fs::write(&path, actual).unwrap();
+        return;
+    }
+
+    let expected = fs::read_to_string(&path).unwrap_or_default();
+    assert_eq!(
+        actual, expected,
+        "the snapshot `{name}` changed (run with UPDATE_SNAPSHOTS=1 to update it)"
+    );
+}
+
+#[test]
+fn bad_instruction() {
+    let dir = spec_with(&manifest(), "//~ spec:startcod\nfn main() {}\n");
+    assert_snapshot("bad_instruction", &render_error(&dir));
+}
+
+#[test]
+fn missing_endcode() {
+    let dir = spec_with(&manifest(), "//~ intro\n//~ spec:startcode\nfn main() {}\n");
+    assert_snapshot("missing_endcode", &render_error(&dir));
+}
+
+#[test]
+fn item_in_code() {
+    let dir = spec_with(
+        &manifest(),
+        "//~ spec:startcode\n//~ spec:item\npub struct State;\n//~ spec:endcode\n",
+    );
+    assert_snapshot("item_in_code", &render_error(&dir));
+}
+
+#[test]
+fn missing_endif() {
+    let dir = spec_with(
+        &manifest(),
+        "//~ intro\n//~ spec:if(internal)\n//~ secret\n",
+    );
+    assert_snapshot("missing_endif", &render_error(&dir));
+}
+
+#[test]
+fn missing_file() {
+    let dir = spec_with(&manifest(), "//~ intro\n");
+    fs::remove_file(dir.path().join("other.rs")).unwrap();
+    assert_snapshot("missing_file", &render_error(&dir));
+}
+
+#[test]
+fn unknown_output_profile() {
+    let manifest = format!(
+        "{}\n[profiles]\ninternal = []\n\n[[outputs]]\nformat = \"html\"\nprofile = \"intenral\"\n",
+        manifest()
+    );
+    let dir = spec_with(&manifest, "//~ intro\n");
+    assert_snapshot("unknown_output_profile", &render_error(&dir));
+}
+
+#[test]
+fn bad_manifest() {
+    let dir = spec_with(&manifest().replace("authors = []", "authors = ["), "");
+    assert_snapshot("bad_manifest", &render_error(&dir));
+}
+
+#[test]
+fn missing_template() {
+    let dir = spec_with(&manifest().replace("template.md", "tempate.md"), "");
+    assert_snapshot("missing_template", &render_error(&dir));
+}
+
+#[test]
+fn bad_code_option() {
+    let dir = spec_with(
+        &manifest(),
+        "//~ spec:startcode(linenos, highlight=5-3)\nfn main() {}\n//~ spec:endcode\n",
+    );
+    assert_snapshot("bad_code_option", &render_error(&dir));
+}
+
+#[test]
+fn unused_section() {
+    let dir = spec_with(
+        &format!("{}unused = \"other.rs\"\n", manifest()),
+        "//~ intro\n",
+    );
+    let outputs = render_targets(&manifest_path(&dir), &[]).unwrap();
+    let warning = &outputs[0].1.warnings[0];
+    assert_snapshot("unused_section", &render_report(&dir, warning.as_ref()));
+}
+
+#[test]
+fn missing_reference_with_text() {
+    let dir = spec_with(
+        &manifest(),
+        "//~ See {ref:handshake-mesage|the handshake}.\n",
+    );
+    assert_snapshot("missing_reference_with_text", &render_error(&dir));
+}
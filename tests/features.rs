@@ -0,0 +1,217 @@
+mod common;
+
+use cargo_spec::build::{render_file_edition, Edition, OutputFormat};
+use common::{manifest_path, MANIFEST};
+use std::path::Path;
+
+/// A specification with profiles, whose template shows the features
+fn spec_with(source: &str) -> tempfile::TempDir {
+    let manifest =
+        format!("{MANIFEST}\n[profiles]\npublic = []\ninternal = [\"internal\", \"drafts\"]\n");
+    common::spec_with(
+        &manifest,
+        "{% if \"internal\" in features %}Internal edition{% if profile %} ({{ profile }}){% endif %}\n\n{% endif %}{{ sections.code }}",
+        &[("lib.rs", source)],
+    )
+}
+
+const SOURCE: &str = "//~ Everyone reads this.
+//~ spec:if(internal)
+//~ Only the team reads this.
+//~ spec:if(!drafts)
+//~ Nobody reads this.
+//~ spec:endif
+//~ spec:endif
+//~ spec:if(!internal)
+//~ Only the public reads this.
+//~ spec:endif
+";
+
+fn render(dir: &tempfile::TempDir, edition: &Edition) -> String {
+    let output =
+        render_file_edition(&manifest_path(dir), None, OutputFormat::Markdown, edition).unwrap();
+    output.files[0].as_str().unwrap().to_string()
+}
+
+#[test]
+fn includes_content_depending_on_features() {
+    let dir = spec_with(SOURCE);
+
+    assert_eq!(
+        render(&dir, &Edition::profile("public")),
+        "Everyone reads this.\nOnly the public reads this.\n"
+    );
+    assert_eq!(
+        render(&dir, &Edition::profile("internal")),
+        "Internal edition (internal)\n\nEveryone reads this.\nOnly the team reads this.\n"
+    );
+    assert_eq!(
+        render(
+            &dir,
+            &Edition {
+                profile: None,
+                features: vec!["internal".to_string()],
+            }
+        ),
+        "Internal edition\n\nEveryone reads this.\nOnly the team reads this.\nNobody reads this.\n"
+    );
+}
+
+#[test]
+fn names_outputs_after_profiles() {
+    assert_eq!(
+        Edition::profile("internal").output_path(Path::new("out/specification.md")),
+        Path::new("out/specification-internal.md")
+    );
+    assert_eq!(
+        Edition::profile("internal").output_path(Path::new("book")),
+        Path::new("book-internal")
+    );
+    assert_eq!(
+        Edition::default().output_path(Path::new("specification.md")),
+        Path::new("specification.md")
+    );
+}
+
+#[test]
+fn reports_unknown_profiles_and_features() {
+    let dir = spec_with(SOURCE);
+    let spec = manifest_path(&dir);
+
+    let error = render_file_edition(
+        &spec,
+        None,
+        OutputFormat::Markdown,
+        &Edition::profile("internl"),
+    )
+    .unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        "Profile `internl` not found in the specification file"
+    );
+
+    let dir = spec_with("//~ spec:if(interal)\n//~ Hidden.\n//~ spec:endif\n");
+    let spec = manifest_path(&dir);
+    let output = render_file_edition(
+        &spec,
+        None,
+        OutputFormat::Markdown,
+        &Edition::profile("public"),
+    )
+    .unwrap();
+    assert_eq!(output.warnings.len(), 1);
+    assert_eq!(
+        output.warnings[0].help().unwrap().to_string(),
+        "did you mean `internal`?"
+    );
+}
+
+#[test]
+fn conditions_can_contain_code_or_be_part_of_it() {
+    let dir = spec_with(
+        "//~ spec:if(internal)
+//~ spec:startcode
+fn internal() {}
+//~ spec:endcode
+//~ spec:endif
+//~ spec:startcode
+fn public() {}
+//~ spec:if(internal)
+fn also_internal() {}
+//~ spec:endif
+//~ spec:endcode
+",
+    );
+
+    assert_eq!(
+        render(&dir, &Edition::profile("public")),
+        "```rust\nfn public() {}\n```\n"
+    );
+    assert_eq!(
+        render(&dir, &Edition::profile("internal")),
+        "Internal edition (internal)\n\n```rust\nfn internal() {}\n```\n```rust\nfn public() {}\nfn also_internal() {}\n```\n"
+    );
+}
+
+#[test]
+fn conditions_cant_straddle_code() {
+    // whether the condition is met or not, it ends in another code block than the one it starts in
+    let sources = [
+        "//~ spec:startcode\nfn a() {}\n//~ spec:if(internal)\n//~ spec:endcode\n//~ spec:endif\n",
+        "//~ spec:if(internal)\n//~ spec:startcode\nfn a() {}\n//~ spec:endif\n//~ spec:endcode\n",
+        "//~ spec:startcode\n//~ spec:if(internal)\n//~ spec:endcode\n//~ spec:startcode\n//~ spec:endif\n//~ spec:endcode\n",
+    ];
+    for source in sources {
+        let dir = spec_with(source);
+        for profile in ["public", "internal"] {
+            let error = render_file_edition(
+                &manifest_path(&dir),
+                None,
+                OutputFormat::Markdown,
+                &Edition::profile(profile),
+            )
+            .unwrap_err();
+            let problem = error.related().unwrap().next().unwrap();
+
+            let start = source.find("if(").unwrap();
+            let labels: Vec<_> = problem
+                .labels()
+                .unwrap()
+                .map(|label| label.offset())
+                .collect();
+            assert_eq!(labels, [start, source.find("endif").unwrap()], "{source}");
+        }
+    }
+}
+
+#[test]
+fn skipped_code_does_not_change_the_indentation() {
+    let dir = spec_with(
+        "impl Node {
+    //~ spec:startcode
+    fn public() {}
+//~ spec:if(internal)
+fn internal() {}
+//~ spec:endif
+    //~ spec:endcode
+}
+",
+    );
+
+    assert_eq!(
+        render(&dir, &Edition::profile("public")),
+        "```rust\nfn public() {}\n```\n"
+    );
+    assert_eq!(
+        render(&dir, &Edition::profile("internal")),
+        "Internal edition (internal)\n\n```rust\n    fn public() {}\nfn internal() {}\n```\n"
+    );
+}
+
+#[test]
+fn nested_conditions_do_not_change_the_indentation() {
+    // the inner condition would be met in the public edition, but the outer one isn't
+    let dir = spec_with(
+        "impl Node {
+    //~ spec:startcode
+    fn public() {}
+    //~ spec:if(internal)
+    fn internal() {}
+//~ spec:if(!internal)
+fn never() {}
+//~ spec:endif
+    //~ spec:endif
+    //~ spec:endcode
+}
+",
+    );
+
+    assert_eq!(
+        render(&dir, &Edition::profile("public")),
+        "```rust\nfn public() {}\n```\n"
+    );
+    assert_eq!(
+        render(&dir, &Edition::profile("internal")),
+        "Internal edition (internal)\n\n```rust\nfn public() {}\nfn internal() {}\n```\n"
+    );
+}
@@ -0,0 +1,93 @@
+mod common;
+
+use cargo_spec::build::OutputFormat;
+use common::{render, render_text, spec_with, MANIFEST};
+
+const TEMPLATE: &str = "{{ fragments.code.handshake }}
+---
+{{ fragments.code.code }}
+---
+{{ sections.code }}";
+
+#[test]
+fn extracts_named_fragments() {
+    let dir = spec_with(
+        MANIFEST,
+        TEMPLATE,
+        &[(
+            "lib.rs",
+            "//~ Intro.
+//~ spec:begin(handshake)
+//~ The handshake starts with a hello.
+//~ spec:begin(code)
+//~ spec:startcode
+fn hello() {}
+//~ spec:endcode
+//~ spec:end(code)
+//~ spec:end(handshake)
+//~ Outro.
+//~ spec:begin(handshake)
+//~ It ends with a goodbye.
+//~ spec:end(handshake)
+",
+        )],
+    );
+
+    assert_eq!(
+        render_text(&dir, OutputFormat::Markdown),
+        "The handshake starts with a hello.
+```rust
+fn hello() {}
+```
+It ends with a goodbye.
+
+---
+```rust
+fn hello() {}
+```
+
+---
+Intro.
+The handshake starts with a hello.
+```rust
+fn hello() {}
+```
+Outro.
+It ends with a goodbye.
+"
+    );
+}
+
+#[test]
+fn fragments_within_code() {
+    let dir = spec_with(
+        MANIFEST,
+        "{{ fragments.code.code }}",
+        &[(
+            "lib.rs",
+            "//~ spec:startcode
+fn hello() {
+    //~ spec:begin(code)
+    send();
+    //~ spec:end(code)
+}
+//~ spec:endcode
+",
+        )],
+    );
+    assert_eq!(render_text(&dir, OutputFormat::Markdown), "    send();\n");
+}
+
+#[test]
+fn rejects_fragments_across_code() {
+    for source in [
+        "//~ spec:begin(code)\n//~ spec:startcode\nfn hello() {}\n//~ spec:end(code)\n//~ spec:endcode\n",
+        "//~ spec:startcode\n//~ spec:begin(code)\nfn hello() {}\n//~ spec:endcode\n//~ spec:end(code)\n",
+    ] {
+        let dir = spec_with(MANIFEST, "{{ sections.code }}", &[("lib.rs", source)]);
+        let error = render(&dir, OutputFormat::Markdown).unwrap_err();
+        let error = format!("{error:?}");
+        assert!(error.contains("the fragment starts here"), "{error}");
+        assert!(error.contains("and ends here"), "{error}");
+    }
+}
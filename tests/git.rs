@@ -0,0 +1,179 @@
+mod common;
+
+use cargo_spec::build::{build_targets, check, OutputFormat, Target};
+use common::{manifest_path, MANIFEST};
+use std::{fs, path::Path, process::Command};
+use tempfile::TempDir;
+
+const SOURCE: &str = "//~ Some structures:
+//~ spec:startcode
+pub struct A;
+pub struct B;
+//~ spec:endcode
+//~ spec:startcode
+pub struct C;
+//~ spec:endcode
+";
+
+fn git(dir: &Path, args: &[&str]) -> String {
+    let output = Command::new("git")
+        .arg("-C")
+        .arg(dir)
+        .args(["-c", "user.name=test", "-c", "user.email=test@example.com"])
+        .args(["-c", "commit.gpgsign=false"])
+        .args(args)
+        .output()
+        .expect("couldn't run git");
+    assert!(output.status.success(), "git {args:?} failed");
+    String::from_utf8(output.stdout).unwrap().trim().to_string()
+}
+
+/// Creates a throwaway git repository containing a specification,
+/// with the given remotes, and returns it along with its HEAD commit.
+fn repo_with_remotes(remotes: &[(&str, &str)]) -> (TempDir, String) {
+    let dir = common::spec_with(
+        &MANIFEST.replace("lib.rs", "src/lib.rs"),
+        "{{ sections.code }}",
+        &[("src/lib.rs", SOURCE)],
+    );
+    let root = dir.path();
+
+    git(root, &["init", "--quiet"]);
+    for (name, url) in remotes {
+        git(root, &["remote", "add", name, url]);
+    }
+    git(root, &["add", "."]);
+    git(root, &["commit", "--quiet", "-m", "init"]);
+    let commit = git(root, &["rev-parse", "HEAD"]);
+
+    (dir, commit)
+}
+
+/// The specification, written next to the specification file
+fn target(dir: &TempDir) -> Target {
+    Target {
+        path: Some(dir.path().join("specification.md")),
+        ..Target::new(OutputFormat::Markdown)
+    }
+}
+
+fn build_spec(dir: &TempDir) -> String {
+    build_targets(manifest_path(dir), &[target(dir)]).unwrap();
+    fs::read_to_string(dir.path().join("specification.md")).unwrap()
+}
+
+#[test]
+fn github_ssh_remote() {
+    let (dir, commit) = repo_with_remotes(&[("origin", "git@github.com:mimoo/example.git")]);
+    let spec = build_spec(&dir);
+
+    let url = format!("https://github.com/mimoo/example/blob/{commit}/src/lib.rs");
+    assert!(spec.contains(&format!("[source]({url}#L3-L4)")), "{spec}");
+    assert!(spec.contains(&format!("[source]({url}#L7)")), "{spec}");
+}
+
+#[test]
+fn github_https_remote() {
+    let (dir, commit) =
+        repo_with_remotes(&[("origin", "https://user@github.com/mimoo/example.git")]);
+    let spec = build_spec(&dir);
+
+    let url = format!("https://github.com/mimoo/example/blob/{commit}/src/lib.rs#L3-L4");
+    assert!(spec.contains(&url), "{spec}");
+}
+
+#[test]
+fn gitlab_ssh_remote_with_port() {
+    let (dir, commit) = repo_with_remotes(&[(
+        "origin",
+        "ssh://git@gitlab.com:2222/group/subgroup/example.git",
+    )]);
+    let spec = build_spec(&dir);
+
+    let url = format!("https://gitlab.com/group/subgroup/example/-/blob/{commit}/src/lib.rs");
+    assert!(spec.contains(&format!("[source]({url}#L3-4)")), "{spec}");
+    assert!(spec.contains(&format!("[source]({url}#L7)")), "{spec}");
+}
+
+#[test]
+fn gitlab_https_remote() {
+    let (dir, commit) = repo_with_remotes(&[("origin", "https://gitlab.com/group/example")]);
+    let spec = build_spec(&dir);
+
+    let url = format!("https://gitlab.com/group/example/-/blob/{commit}/src/lib.rs#L3-4");
+    assert!(spec.contains(&url), "{spec}");
+}
+
+#[test]
+fn gitlab_https_remote_with_port() {
+    let (dir, commit) = repo_with_remotes(&[(
+        "origin",
+        "https://git@gitlab.example.com:8443/group/example.git",
+    )]);
+    let spec = build_spec(&dir);
+
+    let url =
+        format!("https://gitlab.example.com:8443/group/example/-/blob/{commit}/src/lib.rs#L3-4");
+    assert!(spec.contains(&url), "{spec}");
+}
+
+#[test]
+fn prefers_origin_remote() {
+    let (dir, commit) = repo_with_remotes(&[
+        ("fork", "git@github.com:someone/example.git"),
+        ("origin", "git@github.com:mimoo/example.git"),
+    ]);
+    let spec = build_spec(&dir);
+
+    let url = format!("https://github.com/mimoo/example/blob/{commit}/src/lib.rs#L3-L4");
+    assert!(spec.contains(&url), "{spec}");
+}
+
+#[test]
+fn no_permalinks_without_known_remote() {
+    let (dir, _) = repo_with_remotes(&[]);
+    assert!(!build_spec(&dir).contains("[source]"));
+
+    let (dir, _) = repo_with_remotes(&[("origin", "git@bitbucket.org:mimoo/example.git")]);
+    assert!(!build_spec(&dir).contains("[source]"));
+
+    let (dir, _) = repo_with_remotes(&[("origin", "/some/local/path")]);
+    assert!(!build_spec(&dir).contains("[source]"));
+}
+
+#[test]
+fn permalinks_point_to_the_last_commit_of_the_file() {
+    let (dir, commit) = repo_with_remotes(&[("origin", "git@github.com:mimoo/example.git")]);
+    let root = dir.path();
+    let spec = build_spec(&dir);
+
+    // committing the specification (or anything else) doesn't make it outdated
+    git(root, &["add", "."]);
+    git(root, &["commit", "--quiet", "-m", "spec"]);
+    assert_ne!(git(root, &["rev-parse", "HEAD"]), commit);
+    check(manifest_path(&dir), &[target(&dir)]).unwrap();
+    assert_eq!(build_spec(&dir), spec);
+
+    // but changing the file does
+    fs::write(root.join("src/lib.rs"), format!("{SOURCE}// more\n")).unwrap();
+    git(root, &["commit", "--quiet", "-am", "code"]);
+    let new_commit = git(root, &["rev-parse", "HEAD"]);
+    assert!(check(manifest_path(&dir), &[target(&dir)]).is_err());
+    assert!(build_spec(&dir).contains(&format!("/blob/{new_commit}/src/lib.rs#L3-L4")));
+}
+
+#[test]
+fn no_permalinks_for_uncommitted_changes() {
+    let (dir, _) = repo_with_remotes(&[("origin", "git@github.com:mimoo/example.git")]);
+    let root = dir.path();
+
+    fs::write(root.join("src/lib.rs"), format!("// more\n{SOURCE}")).unwrap();
+    assert!(!build_spec(&dir).contains("[source]"));
+
+    git(root, &["add", "."]);
+    assert!(!build_spec(&dir).contains("[source]"));
+
+    git(root, &["commit", "--quiet", "-m", "code"]);
+    let commit = git(root, &["rev-parse", "HEAD"]);
+    assert!(build_spec(&dir).contains(&format!("/blob/{commit}/src/lib.rs#L4-L5")));
+}
@@ -0,0 +1,56 @@
+mod common;
+
+use cargo_spec::build::{render_file, OutputFormat};
+use common::{render, render_text, spec_with, write};
+
+const MANIFEST: &str = "[metadata]
+name = \"test\"
+authors = []
+
+[config]
+template = \"template.md\"
+
+[sections]
+code = \"src/*.rs\"
+";
+
+#[test]
+fn matches_files_in_order() {
+    let dir = spec_with(
+        MANIFEST,
+        "{{ sections.code }}",
+        &[
+            ("src/b.rs", "//~ From b.\n"),
+            ("src/a.rs", "//~ From a.\n"),
+            ("src/notes.txt", "//~ Not code.\n"),
+        ],
+    );
+
+    assert_eq!(
+        render_text(&dir, OutputFormat::Markdown),
+        "From a.\n\nFrom b.\n"
+    );
+}
+
+#[test]
+fn fails_when_nothing_matches() {
+    let dir = spec_with(MANIFEST, "{{ sections.code }}", &[("lib.rs", "")]);
+
+    let err = render(&dir, OutputFormat::Markdown).unwrap_err();
+    assert!(format!("{err:?}")
+        .contains("The pattern `src/*.rs` of section code does not match any file"));
+}
+
+#[test]
+fn escapes_the_specification_directory() {
+    // `[v1]` would otherwise be read as a character class
+    let dir = tempfile::tempdir().unwrap();
+    write(&dir, "spec[v1]/Specification.toml", MANIFEST);
+    write(&dir, "spec[v1]/template.md", "{{ sections.code }}");
+    write(&dir, "spec[v1]/src/lib.rs", "//~ Versioned.\n");
+    write(&dir, "specv/src/lib.rs", "//~ Wrong directory.\n");
+
+    let manifest = dir.path().join("spec[v1]/Specification.toml");
+    let output = render_file(&manifest, None, OutputFormat::Markdown).unwrap();
+    assert_eq!(output.files[0].as_str(), Some("Versioned.\n"));
+}
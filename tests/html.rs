@@ -0,0 +1,86 @@
+mod common;
+
+use cargo_spec::build::OutputFormat;
+use common::{render_text, spec_with, MANIFEST};
+
+const TEMPLATE: &str = "# Introduction
+
+Some text.
+
+## Terms & definitions
+
+{{ sections.code }}
+
+## Terms & definitions
+
+# Protocol
+
+### The `Handshake` type
+";
+
+fn html() -> String {
+    let manifest = MANIFEST.replace(
+        "authors = []",
+        "description = \"A <test> protocol\"\nversion = \"1.0\"\nauthors = [\"Alice\", \"Bob\"]",
+    );
+    let dir = spec_with(&manifest, TEMPLATE, &[("lib.rs", "//~ A *term*.\n")]);
+    render_text(&dir, OutputFormat::Html)
+}
+
+#[test]
+fn renders_a_standalone_page() {
+    let html = html();
+
+    assert!(html.starts_with("<!DOCTYPE html>"));
+    assert!(html.contains("<title>test</title>"));
+    assert!(html.contains("<p>A &lt;test&gt; protocol</p>"));
+    assert!(html.contains("<p>Version 1.0</p>"));
+    assert!(html.contains("<p>Alice, Bob</p>"));
+    assert!(html.contains("<p>A <em>term</em>.</p>"));
+
+    // nothing is loaded from the network
+    assert!(html.contains("<style>"));
+    assert!(!html.contains("<script"));
+    assert!(!html.contains("<link"));
+}
+
+#[test]
+fn links_headers_from_the_table_of_contents() {
+    let html = html();
+
+    assert!(html.contains(
+        "<nav>
+		<ul><li><a href=\"#introduction\">Introduction</a>\
+<ul><li><a href=\"#terms--definitions\">Terms &amp; definitions</a></li>\
+<li><a href=\"#terms--definitions-1\">Terms &amp; definitions</a></li></ul></li>\
+<li><a href=\"#protocol\">Protocol</a>\
+<ul><li><a href=\"#the-handshake-type\">The Handshake type</a></li></ul></li></ul>
+	</nav>"
+    ));
+
+    for id in [
+        "introduction",
+        "terms--definitions",
+        "terms--definitions-1",
+        "protocol",
+        "the-handshake-type",
+    ] {
+        assert!(
+            html.contains(&format!("class=\"anchor\" id=\"{id}\"")),
+            "{id}"
+        );
+    }
+}
+
+#[test]
+fn has_a_single_table_of_contents() {
+    let manifest = MANIFEST.replace("[sections]", "toc = true\n\n[sections]");
+    let dir = spec_with(&manifest, TEMPLATE, &[("lib.rs", "//~ A *term*.\n")]);
+    let html = render_text(&dir, OutputFormat::Html);
+
+    assert_eq!(html.matches("<nav>").count(), 1);
+    assert_eq!(
+        html.matches("<a href=\"#protocol\">Protocol</a>").count(),
+        1
+    );
+}
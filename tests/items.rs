@@ -0,0 +1,196 @@
+mod common;
+
+use cargo_spec::build::OutputFormat;
+use common::{render, render_text, spec_with, MANIFEST};
+
+#[test]
+fn extracts_the_item_that_follows() {
+    let dir = spec_with(
+        MANIFEST,
+        "{{ sections.code }}",
+        &[(
+            "lib.rs",
+            "//~ The state:
+//~ spec:item
+#[derive(Debug)]
+pub struct State {
+    counter: u64,
+}
+
+//~ How to send a message:
+//~ spec:item
+pub fn send(state: &mut State) -> u64 {
+    state.counter += 1;
+    state.counter
+}
+",
+        )],
+    );
+
+    assert_eq!(
+        render_text(&dir, OutputFormat::Markdown),
+        "The state:
+```rust
+#[derive(Debug)]
+pub struct State {
+    counter: u64,
+}
+```
+How to send a message:
+```rust
+pub fn send(state: &mut State) -> u64
+```
+"
+    );
+}
+
+#[test]
+fn requires_an_item() {
+    let dir = spec_with(
+        MANIFEST,
+        "{{ sections.code }}",
+        &[("lib.rs", "pub struct State;\n//~ spec:item\n")],
+    );
+
+    let err = format!("{:?}", render(&dir, OutputFormat::Markdown).unwrap_err());
+    assert!(err.contains("spec:item must be directly followed by a Rust item"));
+}
+
+#[test]
+fn requires_the_item_to_be_next() {
+    for source in [
+        "impl State {\n    fn send(&self) {}\n    //~ spec:item\n}\n\npub struct Other;\n",
+        "fn send() {\n    //~ spec:item\n    let counter = 1;\n    fn inner() {}\n}\n\npub struct Other;\n",
+    ] {
+        let dir = spec_with(MANIFEST, "{{ sections.code }}", &[("lib.rs", source)]);
+        let err = format!("{:?}", render(&dir, OutputFormat::Markdown).unwrap_err());
+        assert!(
+            err.contains("spec:item must be directly followed by a Rust item"),
+            "{err}"
+        );
+    }
+}
+
+/// A crate with its specification in `spec/`, which has the given `[sections]` (rendered as `sections.items`)
+fn crate_with_items(sections: &str) -> tempfile::TempDir {
+    let manifest = format!(
+        "[metadata]
+name = \"test\"
+authors = []
+
+[config]
+template = \"template.md\"
+
+[sections]
+{sections}"
+    );
+    let dir = tempfile::tempdir().unwrap();
+    common::write(&dir, "spec/Specification.toml", &manifest);
+    common::write(&dir, "spec/template.md", "{{ sections.items }}");
+    common::write(&dir, "Cargo.toml", "[package]\nname = \"test\"\n");
+    common::write(
+        &dir,
+        "src/lib.rs",
+        "mod state;
+mod network;
+#[path = \"generated/codec.rs\"]
+mod codec;
+
+/// The version of the protocol
+pub const VERSION: u8 = 1;
+",
+    );
+    common::write(
+        &dir,
+        "src/state.rs",
+        "mod counter;\n\n/// The state of a peer\npub struct State;\n",
+    );
+    common::write(
+        &dir,
+        "src/state/counter.rs",
+        "/// Counts messages\npub fn next(counter: &mut u64) -> u64 {\n    *counter += 1;\n    *counter\n}\n",
+    );
+    common::write(
+        &dir,
+        "src/network/mod.rs",
+        "/// A message\npub enum Message {\n    Ping,\n}\n",
+    );
+    common::write(
+        &dir,
+        "src/generated/codec.rs",
+        "/// Encodes messages\npub trait Codec {}\n",
+    );
+    dir
+}
+
+fn render_items(dir: &tempfile::TempDir) -> miette::Result<String> {
+    let manifest = dir.path().join("spec/Specification.toml");
+    let output = cargo_spec::build::render_file(&manifest, None, OutputFormat::Markdown)?;
+    Ok(output.files[0].as_str().unwrap().to_string())
+}
+
+/// The problem with the (only) entry of the specification that couldn't be used
+fn problem(dir: &tempfile::TempDir) -> String {
+    let err = render_items(dir).unwrap_err();
+    let mut related = err.related().unwrap();
+    related.next().unwrap().to_string()
+}
+
+#[test]
+fn finds_items_by_path() {
+    let dir = crate_with_items(
+        "items = [
+    \"item:crate::VERSION\",
+    \"item:crate::state::State\",
+    \"item:state::counter::next\",
+    \"item:crate::network::Message\",
+    \"item:crate::codec::Codec\",
+]
+",
+    );
+
+    assert_eq!(
+        render_items(&dir).unwrap(),
+        "The version of the protocol
+
+```rust
+pub const VERSION: u8 = 1;
+```
+
+The state of a peer
+
+```rust
+pub struct State;
+```
+
+Counts messages
+
+```rust
+pub fn next(counter: &mut u64) -> u64
+```
+
+A message
+
+```rust
+pub enum Message {
+    Ping,
+}
+```
+
+Encodes messages
+
+```rust
+pub trait Codec {}
+```
+"
+    );
+}
+
+#[test]
+fn reports_missing_items() {
+    let dir = crate_with_items("items = \"item:crate::state::Missing\"\n");
+    assert!(problem(&dir).ends_with("item `Missing` not found"));
+
+    let dir = crate_with_items("items = \"item:crate::storage::State\"\n");
+    assert!(problem(&dir).ends_with("module `storage` not found"));
+}
@@ -0,0 +1,105 @@
+mod common;
+
+use cargo_spec::build::OutputFormat;
+use common::{render_text, spec_with, MANIFEST};
+
+const TEMPLATE: &str = "{{ sections.code }}";
+
+fn manifest(files: &str, languages: &str) -> String {
+    MANIFEST.replace("code = \"lib.rs\"", &format!("code = {files}")) + languages
+}
+
+#[test]
+fn uses_the_builtin_languages() {
+    let dir = spec_with(
+        &manifest("[\"main.py\", \"parser.ml\"]", ""),
+        TEMPLATE,
+        &[
+            (
+                "main.py",
+                "#~ In Python:
+#~ spec:startcode
+def main():
+    pass
+#~ spec:endcode
+",
+            ),
+            (
+                "parser.ml",
+                "(*~ In OCaml,
+   on two lines. *)
+(*~ spec:startcode *)
+let parse x = x
+(*~ spec:endcode *)
+",
+            ),
+        ],
+    );
+
+    assert_eq!(
+        render_text(&dir, OutputFormat::Markdown),
+        "In Python:
+```python
+def main():
+    pass
+```
+
+In OCaml,
+on two lines. 
+```ocaml
+let parse x = x
+```
+"
+    );
+}
+
+#[test]
+fn uses_custom_languages() {
+    let languages = "
+[languages.nu]
+line_comment = \"#\"
+fence = \"nushell\"
+
+[languages.rs]
+fence = \"rs\"
+";
+    let dir = spec_with(
+        &manifest("[\"script.nu\", \"lib.rs\", \"notes.txt\"]", languages),
+        TEMPLATE,
+        &[
+            (
+                "script.nu",
+                "#~ A script:\n#~ spec:startcode\nls | length\n#~ spec:endcode\n",
+            ),
+            // only the fence is overridden, the comments are still the built-in ones
+            (
+                "lib.rs",
+                "/*~ A crate: */\n//~ spec:startcode\npub struct A;\n//~ spec:endcode\n",
+            ),
+            // unknown extensions use `//~`, and their extension as fence
+            (
+                "notes.txt",
+                "//~ Notes:\n//~ spec:startcode\nsome notes\n//~ spec:endcode\n",
+            ),
+        ],
+    );
+
+    assert_eq!(
+        render_text(&dir, OutputFormat::Markdown),
+        "A script:
+```nushell
+ls | length
+```
+
+A crate: 
+```rs
+pub struct A;
+```
+
+Notes:
+```txt
+some notes
+```
+"
+    );
+}
@@ -0,0 +1,139 @@
+mod common;
+
+use cargo_spec::{
+    build::{build, build_script, render, OutputFormat},
+    toml_parser::{Config, Metadata, Section, Specification},
+};
+use common::{manifest_path, spec_with, write, MANIFEST};
+use std::{fs, path::Path};
+
+#[test]
+fn renders_in_memory() {
+    let dir = tempfile::tempdir().unwrap();
+    let root = dir.path();
+    fs::write(
+        root.join("template.md"),
+        "# {{ metadata.name }}\n\n{{ sections.code }}",
+    )
+    .unwrap();
+    fs::write(root.join("lib.rs"), "//~ Some spec\npub struct A;\n").unwrap();
+
+    let specification = Specification {
+        metadata: Metadata {
+            name: "test".to_string(),
+            ..Default::default()
+        },
+        config: Config {
+            template: "template.md".to_string(),
+            ..Default::default()
+        },
+        sections: [("code".to_string(), Section::Single("lib.rs".to_string()))]
+            .into_iter()
+            .collect(),
+        ..Default::default()
+    };
+
+    let output_file = root.join("specification.md");
+    let output = render(
+        &specification,
+        root,
+        Some(output_file.clone()),
+        OutputFormat::Markdown,
+    )
+    .unwrap();
+
+    assert_eq!(output.files.len(), 1);
+    assert_eq!(output.files[0].path, output_file);
+    assert_eq!(output.files[0].as_str(), Some("# test\n\nSome spec\n"));
+    assert!(output.warnings.is_empty());
+    assert!(output.dependencies.contains(&root.join("template.md")));
+    assert!(output.dependencies.contains(&root.join("lib.rs")));
+
+    // nothing was written
+    assert!(!output_file.exists());
+    assert_eq!(fs::read_dir(root).unwrap().count(), 2);
+}
+
+#[test]
+fn build_script_fails_only_when_strict() {
+    let dir = spec_with(
+        MANIFEST,
+        "{{ sections.code }}",
+        &[("lib.rs", "//~ spec:endcode\n")],
+    );
+    let root = dir.path();
+
+    let toml_spec = manifest_path(&dir);
+    let output_file = root.join("specification.md");
+    let format = OutputFormat::Markdown;
+
+    assert!(build_script(&toml_spec, Some(output_file.clone()), format, true).is_err());
+    assert!(build_script(&toml_spec, Some(output_file.clone()), format, false).is_ok());
+    assert!(!output_file.exists());
+
+    write(&dir, "lib.rs", "//~ Some spec\n");
+    build_script(&toml_spec, Some(output_file.clone()), format, true).unwrap();
+    assert_eq!(fs::read_to_string(&output_file).unwrap(), "Some spec\n");
+}
+
+#[test]
+fn builds_a_single_output() {
+    let dir = spec_with(
+        MANIFEST,
+        "{{ sections.code }}",
+        &[("lib.rs", "//~ Some spec\n")],
+    );
+    let output_file = dir.path().join("out/spec.md");
+
+    let dependencies = build(
+        manifest_path(&dir),
+        Some(output_file.clone()),
+        OutputFormat::Markdown,
+    )
+    .unwrap();
+    assert_eq!(fs::read_to_string(&output_file).unwrap(), "Some spec\n");
+    assert!(dependencies.contains(&dir.path().join("lib.rs")));
+}
+
+#[test]
+fn exports_json() {
+    let dir = spec_with(
+        MANIFEST,
+        "{{ sections.code }}",
+        &[(
+            "lib.rs",
+            "//~ Some spec\n//~ on two lines\n//~ spec:startcode\npub struct A;\n//~ spec:endcode\n",
+        )],
+    );
+
+    let output = common::render(&dir, OutputFormat::Json).unwrap();
+    assert_eq!(output.files[0].path, Path::new("specification.json"));
+
+    let json: serde_json::Value = serde_json::from_slice(&output.files[0].content).unwrap();
+    assert_eq!(json["metadata"]["name"], "test");
+    assert_eq!(json["sections"][0]["name"], "code");
+
+    let blocks: Vec<_> = json["sections"][0]["blocks"]
+        .as_array()
+        .unwrap()
+        .iter()
+        .map(|block| {
+            (
+                block["kind"].as_str().unwrap(),
+                block["file"].as_str().unwrap(),
+                block["first_line"].as_u64().unwrap(),
+                block["last_line"].as_u64().unwrap(),
+                block["content"].as_str().unwrap(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        blocks,
+        [
+            ("prose", "lib.rs", 1, 2, "Some spec\non two lines\n"),
+            ("instruction", "lib.rs", 3, 3, "spec:startcode\n"),
+            ("code", "lib.rs", 4, 4, "pub struct A;\n"),
+            ("instruction", "lib.rs", 5, 5, "spec:endcode\n"),
+        ]
+    );
+}
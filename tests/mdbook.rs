@@ -0,0 +1,126 @@
+mod common;
+
+use cargo_spec::build::OutputFormat;
+use common::{render, spec_with, MANIFEST};
+
+const TEMPLATE: &str = "# My Spec
+
+Some introduction.
+
+## Overview
+
+See [the handshake](#handshake).
+
+## Protocol
+
+{{ sections.code }}
+
+### Handshake
+
+## ???
+";
+
+fn book() -> Vec<(String, String)> {
+    let manifest = MANIFEST.replace(
+        "authors = []",
+        "description = \"A test\"\nauthors = [\"Alice\"]",
+    );
+    let dir = spec_with(
+        &manifest,
+        TEMPLATE,
+        &[("lib.rs", "//~ The [overview](#overview).\n")],
+    );
+
+    render(&dir, OutputFormat::Mdbook)
+        .unwrap()
+        .files
+        .into_iter()
+        .map(|file| {
+            let path = file.path.display().to_string();
+            (path, file.as_str().unwrap().to_string())
+        })
+        .collect()
+}
+
+#[test]
+fn splits_the_specification_into_chapters() {
+    let book = book();
+    let paths: Vec<_> = book.iter().map(|(path, _)| path.as_str()).collect();
+    assert_eq!(
+        paths,
+        [
+            "book/book.toml",
+            "book/src/README.md",
+            "book/src/overview.md",
+            "book/src/protocol.md",
+            // a header without an id is named after its position
+            "book/src/section-3.md",
+            "book/src/SUMMARY.md",
+        ]
+    );
+
+    assert_eq!(
+        book[0].1,
+        "[book]\ntitle = \"test\"\nauthors = [\"Alice\"]\ndescription = \"A test\"\nsrc = \"src\"\n"
+    );
+    assert_eq!(book[1].1, "\nSome introduction.\n\n");
+    assert_eq!(book[4].1, "## ???\n");
+    assert_eq!(
+        book[5].1,
+        "# Summary
+
+[Introduction](README.md)
+
+- [Overview](overview.md)
+- [Protocol](protocol.md)
+- [???](section-3.md)
+"
+    );
+}
+
+#[test]
+fn links_to_other_chapters() {
+    let book = book();
+
+    assert_eq!(
+        book[2].1,
+        "## Overview\n\nSee [the handshake](protocol.md#handshake).\n\n"
+    );
+    assert_eq!(
+        book[3].1,
+        "## Protocol\n\nThe [overview](overview.md#overview).\n\n\n### Handshake\n\n"
+    );
+}
+
+#[test]
+fn does_not_replace_the_summary_or_the_introduction() {
+    let dir = spec_with(
+        MANIFEST,
+        "# My Spec\n\n## Summary\n\n{{ sections.code }}\n\n## Readme\n",
+        &[("lib.rs", "//~ See the [readme](#readme).\n")],
+    );
+
+    let book: Vec<_> = render(&dir, OutputFormat::Mdbook)
+        .unwrap()
+        .files
+        .into_iter()
+        .map(|file| {
+            let path = file.path.display().to_string();
+            (path, file.as_str().unwrap().to_string())
+        })
+        .collect();
+    let paths: Vec<_> = book.iter().map(|(path, _)| path.as_str()).collect();
+    assert_eq!(
+        paths,
+        [
+            "book/book.toml",
+            "book/src/summary-chapter.md",
+            "book/src/readme-chapter.md",
+            "book/src/SUMMARY.md",
+        ]
+    );
+    assert_eq!(
+        book[1].1,
+        "## Summary\n\nSee the [readme](readme-chapter.md#readme).\n\n\n"
+    );
+}
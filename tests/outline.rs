@@ -0,0 +1,33 @@
+mod common;
+
+use cargo_spec::build::OutputFormat;
+use common::{render_text, spec_with, MANIFEST};
+
+#[test]
+fn numbers_headers_and_inserts_toc() {
+    let manifest = MANIFEST.replace("[sections]", "numbering = true\ntoc = true\n\n[sections]");
+    let dir = spec_with(
+        &manifest,
+        "# Test\n\n## Overview\n\n{{ sections.code }}\n\n## Handshake\n\n### Handshake message\n",
+        &[("lib.rs", "//~ See {ref:handshake-message}.\n")],
+    );
+
+    assert_eq!(
+        render_text(&dir, OutputFormat::Markdown),
+        "# Test
+
+- [1 Overview](#1-overview)
+- [2 Handshake](#2-handshake)
+  - [2.1 Handshake message](#21-handshake-message)
+
+## 1 Overview
+
+See [2.1 Handshake message](#21-handshake-message).
+
+
+## 2 Handshake
+
+### 2.1 Handshake message
+"
+    );
+}
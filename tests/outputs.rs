@@ -0,0 +1,108 @@
+mod common;
+
+use cargo_spec::build::{render_targets, OutputFormat, Target};
+use common::manifest_path;
+
+/// A specification file with profiles, and outputs for each of them
+fn manifest() -> String {
+    let manifest = common::MANIFEST.replace("[sections]", "numbering = true\n\n[sections]");
+    format!(
+        r#"{manifest}
+[profiles]
+public = []
+internal = ["internal"]
+
+[[outputs]]
+format = "markdown"
+profile = "public"
+
+[[outputs]]
+format = "json"
+path = "out/spec.json"
+profile = "public"
+
+[[outputs]]
+format = "markdown"
+path = "internal.md"
+profile = "internal"
+numbering = false
+"#
+    )
+}
+
+fn spec_with(manifest: &str) -> tempfile::TempDir {
+    common::spec_with(
+        manifest,
+        "## Code\n\n{{ sections.code }}",
+        &[(
+            "lib.rs",
+            "//~ Everyone reads this.\n//~ spec:if(internal)\n//~ Only the team reads this.\n//~ spec:endif\n//~ spec:if(drafts)\n//~ spec:endif\n",
+        )],
+    )
+}
+
+#[test]
+fn builds_the_outputs_of_the_manifest() {
+    let dir = spec_with(&manifest());
+    let spec = manifest_path(&dir);
+
+    let outputs = render_targets(&spec, &[]).unwrap();
+    let paths: Vec<_> = outputs.iter().map(|(path, _)| path.clone()).collect();
+    assert_eq!(
+        paths,
+        [
+            dir.path().join("specification.md"),
+            dir.path().join("out/spec.json"),
+            dir.path().join("internal.md"),
+        ]
+    );
+
+    let content = |index: usize| outputs[index].1.files[0].as_str().unwrap().to_string();
+    assert_eq!(content(0), "## 1 Code\n\nEveryone reads this.\n");
+    assert!(content(1).contains("\"content\": \"Everyone reads this.\\n\""));
+    assert_eq!(
+        content(2),
+        "## Code\n\nEveryone reads this.\nOnly the team reads this.\n"
+    );
+
+    // the files are parsed once per edition, so their warnings are only reported once
+    let warnings: Vec<_> = outputs
+        .iter()
+        .map(|(_, output)| output.warnings.len())
+        .collect();
+    assert_eq!(warnings, [1, 0, 1]);
+}
+
+#[test]
+fn given_targets_replace_the_outputs_of_the_manifest() {
+    let dir = spec_with(&manifest());
+    let spec = manifest_path(&dir);
+
+    let target = Target {
+        path: Some(dir.path().join("other.html")),
+        ..Target::new(OutputFormat::Html)
+    };
+    let outputs = render_targets(&spec, &[target]).unwrap();
+    assert_eq!(outputs.len(), 1);
+    assert_eq!(outputs[0].0, dir.path().join("other.html"));
+    assert!(outputs[0].1.files[0]
+        .as_str()
+        .unwrap()
+        .contains("Everyone reads this."));
+}
+
+#[test]
+fn rejects_outputs_at_the_same_path() {
+    let manifest = manifest().replace("path = \"internal.md\"", "path = \"specification.md\"");
+    let dir = spec_with(&manifest);
+    let spec = manifest_path(&dir);
+
+    let error = render_targets(&spec, &[]).unwrap_err();
+    assert_eq!(
+        error.to_string(),
+        format!(
+            "Several outputs are written at {}",
+            dir.path().join("specification.md").display()
+        )
+    );
+}
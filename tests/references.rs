@@ -0,0 +1,35 @@
+mod common;
+
+use cargo_spec::build::OutputFormat;
+use common::{render, MANIFEST};
+
+fn spec_with(source: &str) -> tempfile::TempDir {
+    common::spec_with(
+        MANIFEST,
+        "## Handshake message\n\n{{ sections.code }}",
+        &[("lib.rs", source)],
+    )
+}
+
+#[test]
+fn resolves_references() {
+    let dir = spec_with(
+        "//~ spec:anchor(nonce)\n//~ See {ref:handshake-message}, {ref:nonce}, and `{ref:code}`.\n",
+    );
+    let output = render(&dir, OutputFormat::Markdown).unwrap();
+
+    assert_eq!(
+        output.files[0].as_str(),
+        Some("## Handshake message\n\n<a id=\"nonce\"></a>\n\nSee [Handshake message](#handshake-message), [nonce](#nonce), and `{ref:code}`.\n")
+    );
+}
+
+#[test]
+fn missing_reference_target() {
+    let dir = spec_with("//~ See {ref:handshake-mesage}.\n");
+    let error = render(&dir, OutputFormat::Markdown).unwrap_err();
+
+    assert!(error.to_string().contains("handshake-mesage"), "{error:?}");
+    let help = error.help().map(|help| help.to_string());
+    assert_eq!(help.as_deref(), Some("did you mean `handshake-message`?"));
+}
@@ -0,0 +1,116 @@
+mod common;
+
+use cargo_spec::build::OutputFormat;
+use common::{render, write, MANIFEST};
+use std::path::Path;
+
+const TEMPLATE: &str = "{{ sections.code }}
+{% for req in requirements %}
+- {{ req.id }} ({{ req.file }}:{{ req.line }}): {{ req.text }}
+{%- endfor %}
+";
+
+fn spec_with(source: &str) -> tempfile::TempDir {
+    common::spec_with(MANIFEST, TEMPLATE, &[("lib.rs", source)])
+}
+
+#[test]
+fn indexes_requirements() {
+    let dir = spec_with(
+        "//~ Blocks:
+//~ spec:req(BLOCK-1) The node MUST reject
+//~ blocks from the future.
+//~
+//~ Nodes SHOULD do things, and `MUST` is a keyword.
+",
+    );
+    let output = render(&dir, OutputFormat::Markdown).unwrap();
+
+    assert_eq!(
+        output.files[0].as_str(),
+        Some(
+            "Blocks:
+
+<a id=\"BLOCK-1\"></a>**BLOCK-1**: The node MUST reject
+blocks from the future.
+
+Nodes SHOULD do things, and `MUST` is a keyword.
+
+
+- BLOCK-1 (lib.rs:2): The node MUST reject blocks from the future.
+"
+        )
+    );
+
+    assert_eq!(output.warnings.len(), 1);
+    assert!(output.warnings[0].to_string().contains("SHOULD"));
+}
+
+#[test]
+fn only_checks_keywords_in_spec_comments() {
+    let manifest = MANIFEST.replace("code = \"lib.rs\"", "code = [\"lib.rs\", \"notes.md\"]");
+    let dir = common::spec_with(
+        &manifest,
+        "The template MUST be valid.\n{{ sections.code }}",
+        &[
+            ("lib.rs", "//~ Spec comments MUST be checked.\n"),
+            ("notes.md", "Markdown files SHOULD not be.\n"),
+        ],
+    );
+    let output = render(&dir, OutputFormat::Markdown).unwrap();
+
+    let warnings: Vec<_> = output
+        .warnings
+        .iter()
+        .map(|warning| warning.to_string())
+        .collect();
+    assert_eq!(warnings, ["`MUST` is used outside of a requirement"]);
+}
+
+#[test]
+fn duplicate_requirements() {
+    let dir = spec_with("//~ spec:req(BLOCK-1) first\n//~ spec:req(BLOCK-1) second\n");
+    let error = render(&dir, OutputFormat::Markdown).unwrap_err();
+
+    assert!(error.to_string().contains("BLOCK-1"), "{error:?}");
+}
+
+#[test]
+fn links_tests_to_requirements() {
+    let manifest = MANIFEST.replace("[sections]", "tests = [\"tests/*.rs\"]\n\n[sections]");
+    let dir = common::spec_with(
+        &manifest,
+        TEMPLATE,
+        &[
+            (
+                "lib.rs",
+                "//~ spec:req(BLOCK-1) first\n//~\n//~ spec:req(BLOCK-2) second\n",
+            ),
+            (
+                "tests/blocks.rs",
+                "//~ spec:tests(BLOCK-1)\n#[test]\nfn rejects_blocks() {}\n",
+            ),
+        ],
+    );
+    let root = dir.path();
+
+    let output = render(&dir, OutputFormat::Markdown).unwrap();
+
+    let tests = &output.requirements[0].tests;
+    assert_eq!(tests.len(), 1);
+    assert_eq!(tests[0].name, "rejects_blocks");
+    assert_eq!(tests[0].file, Path::new("tests/blocks.rs"));
+    assert_eq!(tests[0].line, 1);
+    assert!(output.requirements[1].tests.is_empty());
+    assert!(output
+        .dependencies
+        .contains(&root.canonicalize().unwrap().join("tests/blocks.rs")));
+
+    write(
+        &dir,
+        "tests/blocks.rs",
+        "//~ spec:tests(BLOCK-3)\n#[test]\nfn rejects_blocks() {}\n",
+    );
+    let error = render(&dir, OutputFormat::Markdown).unwrap_err();
+    assert!(error.to_string().contains("BLOCK-3"), "{error:?}");
+}
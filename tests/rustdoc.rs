@@ -0,0 +1,165 @@
+mod common;
+
+use cargo_spec::build::OutputFormat;
+use common::{render_text, spec_with, MANIFEST};
+
+#[test]
+fn extracts_doc_comments() {
+    let manifest = MANIFEST.replace(
+        "code = \"lib.rs\"",
+        "api = { files = [\"lib.rs\"], mode = \"docs\" }\nspec = \"lib.rs\"",
+    );
+    let dir = spec_with(
+        &manifest,
+        "# Test\n\n## Config\n\n{{ sections.api }}\n---\n{{ sections.spec }}",
+        &[(
+            "lib.rs",
+            "//! The module, see [`Config`].
+//~ A spec comment.
+
+/// The config.
+///
+/// ```
+/// # use test::Config;
+/// let config = Config::default();
+/// ```
+pub struct Config;
+
+//// not a doc comment
+/// Uses [the config](struct@Config), [`crate::Missing`], and [`Vec`](https://doc.rust-lang.org/std/vec/struct.Vec.html).
+/// See [`Config`][config].
+///
+/// [config]: crate::Config
+pub fn run() {}
+
+//~ spec:startcode
+/// extracted as code
+pub struct Code;
+//~ spec:endcode
+",
+        )],
+    );
+
+    assert_eq!(
+        render_text(&dir, OutputFormat::Markdown),
+"# Test
+
+## Config
+
+The module, see [`Config`](#config).
+A spec comment.
+
+The config.
+
+```rust
+let config = Config::default();
+```
+
+Uses [the config](#config), `crate::Missing`, and [`Vec`](https://doc.rust-lang.org/std/vec/struct.Vec.html).
+See [`Config`](#config).
+
+```rust
+/// extracted as code
+pub struct Code;
+```
+
+---
+A spec comment.
+```rust
+/// extracted as code
+pub struct Code;
+```
+"
+    );
+}
+
+/// A crate whose `src/api.rs` module is in a section in `docs` mode, with the given items
+fn crate_with_items(items: &str) -> tempfile::TempDir {
+    let manifest = MANIFEST.replace(
+        "code = \"lib.rs\"",
+        &format!("api = {{ files = [\"src/api.rs\"], mode = \"docs\", items = {items} }}"),
+    );
+    spec_with(
+        &manifest,
+        "{{ sections.api }}",
+        &[
+            ("Cargo.toml", "[package]\nname = \"test\"\n"),
+            (
+                "src/lib.rs",
+                "pub mod api;\n\n/// The root.\npub struct Root;\n",
+            ),
+            (
+                "src/api.rs",
+                "//! The API.
+
+/// A client.
+pub struct Client;
+
+//~ A spec comment.
+
+/// Connects the client.
+pub fn connect() {}
+
+/// An internal helper.
+fn helper() {}
+",
+            ),
+        ],
+    )
+}
+
+#[test]
+fn extracts_the_doc_comments_of_some_items() {
+    let dir = crate_with_items("[\"crate::api::connect\", \"crate::api::Client\"]");
+
+    assert_eq!(
+        render_text(&dir, OutputFormat::Markdown),
+        "A client.
+A spec comment.
+
+Connects the client.
+"
+    );
+}
+
+#[test]
+fn items_must_be_in_the_section() {
+    let dir = crate_with_items("[\"crate::Root\"]");
+    let error = common::render(&dir, OutputFormat::Markdown).unwrap_err();
+
+    let problem = error.related().unwrap().next().unwrap().to_string();
+    assert_eq!(
+        problem,
+        "The item `crate::Root` is not defined in the files of section api"
+    );
+}
+
+#[test]
+fn keeps_links_that_are_not_intra_doc_links() {
+    let manifest = MANIFEST.replace(
+        "code = \"lib.rs\"",
+        "api = { files = [\"lib.rs\"], mode = \"docs\" }",
+    );
+    let dir = spec_with(
+        &manifest,
+        "## Client\n\n{{ sections.api }}",
+        &[(
+            "lib.rs",
+            "/// See the [guide](README), the [changelog][changes], and the [client](Client).
+///
+/// [changes]: CHANGELOG
+pub struct Client;
+",
+        )],
+    );
+
+    assert_eq!(
+        render_text(&dir, OutputFormat::Markdown),
+        "## Client
+
+See the [guide](README), the [changelog][changes], and the [client](#client).
+
+[changes]: CHANGELOG
+"
+    );
+}
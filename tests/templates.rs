@@ -0,0 +1,184 @@
+mod common;
+
+use cargo_spec::build::OutputFormat;
+use common::{render, spec_with, MANIFEST};
+use miette::{Diagnostic, Report};
+
+fn render_error(template: &str) -> Report {
+    let dir = spec_with(MANIFEST, template, &[("lib.rs", "//~ Some spec\n")]);
+    render(&dir, OutputFormat::Markdown).unwrap_err()
+}
+
+fn help(diagnostic: &dyn Diagnostic) -> Option<String> {
+    diagnostic.help().map(|help| help.to_string())
+}
+
+#[test]
+fn suggests_the_closest_placeholder() {
+    let error = render_error("# Spec\n\n{{ sections.cod }}\n");
+
+    assert_eq!(
+        error.to_string(),
+        "Unknown placeholder `sections.cod` in the template"
+    );
+    assert_eq!(
+        help(&*error).as_deref(),
+        Some("did you mean `sections.code`?")
+    );
+
+    // the error points to the placeholder
+    let labels: Vec<_> = error.labels().unwrap().collect();
+    assert_eq!((labels[0].offset(), labels[0].len()), (11, 12));
+}
+
+#[test]
+fn lists_the_valid_placeholders() {
+    let error = render_error("{{ sections.code }} {{ specification }}");
+
+    assert_eq!(
+        help(&*error).as_deref(),
+        Some("the template only has `config`, `features`, `fragments`, `metadata`, `profile`, `requirements`, `sections`")
+    );
+}
+
+#[test]
+fn reports_every_unknown_placeholder() {
+    let error = render_error("{{ metadata.nme }}\n{{ sections.code }}\n{{ fragments.code.intro }}");
+
+    assert!(error.to_string().ends_with("has 2 problems"), "{error}");
+    let problems: Vec<_> = error
+        .related()
+        .unwrap()
+        .map(|problem| (problem.to_string(), help(problem).unwrap()))
+        .collect();
+    assert_eq!(
+        problems,
+        [
+            (
+                "Unknown placeholder `metadata.nme` in the template".to_string(),
+                "did you mean `metadata.name`?".to_string()
+            ),
+            (
+                "Unknown placeholder `fragments.code.intro` in the template".to_string(),
+                "`fragments.code` has no fields".to_string()
+            ),
+        ]
+    );
+}
+
+#[test]
+fn rejects_legacy_placeholders() {
+    let error = render_error("{sections.code}");
+
+    assert_eq!(
+        help(&*error).as_deref(),
+        Some("placeholders are now written `{{ sections.code }}`")
+    );
+}
+
+#[test]
+fn ignores_legacy_placeholders_in_code() {
+    let template = "{{ sections.code }}
+
+Placeholders used to be written `{sections.code}`:
+
+```md
+{sections.code}
+```
+";
+    let dir = spec_with(MANIFEST, template, &[("lib.rs", "//~ Some spec\n")]);
+    let output = render(&dir, OutputFormat::Markdown).unwrap();
+    assert!(output.files[0]
+        .as_str()
+        .unwrap()
+        .ends_with("```md\n{sections.code}\n```\n"));
+}
+
+#[test]
+fn warns_about_unused_sections() {
+    let manifest = format!("{MANIFEST}other = \"lib.rs\"\n");
+    let dir = spec_with(
+        &manifest,
+        "{{ sections.code }}",
+        &[("lib.rs", "//~ Some spec\n")],
+    );
+    let output = render(&dir, OutputFormat::Markdown).unwrap();
+
+    let warnings: Vec<_> = output
+        .warnings
+        .iter()
+        .map(|warning| warning.to_string())
+        .collect();
+    assert_eq!(warnings, ["Section `other` is never used in the template"]);
+}
+
+/// A specification with two sections, `code` and `other`
+fn spec_with_sections(template: &str, files: &[(&str, &str)]) -> tempfile::TempDir {
+    let manifest = format!("{MANIFEST}other = \"other.rs\"\n");
+    let files = [
+        &[("lib.rs", "//~ Some spec\n"), ("other.rs", "//~ Other\n")],
+        files,
+    ]
+    .concat();
+    spec_with(&manifest, template, &files)
+}
+
+#[test]
+fn includes_templates_and_loops_over_sections() {
+    let dir = spec_with_sections(
+        "# {{ metadata.name }}
+{% include \"chapters/intro.md\" %}
+{% for name, content in sections|items %}
+## {{ name }}
+
+{{ content }}{% endfor %}
+{% if metadata.version %}Version {{ metadata.version }}{% endif %}",
+        &[("chapters/intro.md", "Intro of {{ metadata.name }}.\n")],
+    );
+    let output = render(&dir, OutputFormat::Markdown).unwrap();
+
+    assert_eq!(
+        output.files[0].as_str(),
+        Some("# test\nIntro of test.\n\n\n## code\n\nSome spec\n\n## other\n\nOther\n\n")
+    );
+    // the loop uses every section
+    assert!(output.warnings.is_empty());
+    // the specification is built again when an included template changes
+    assert!(output
+        .dependencies
+        .contains(&dir.path().join("chapters/intro.md")));
+}
+
+#[test]
+fn checks_included_templates() {
+    let dir = spec_with_sections(
+        "{% include \"chapters/intro.md\" %}",
+        &[(
+            "chapters/intro.md",
+            "{{ sections.code }}\n{{ sections.othr }}\n",
+        )],
+    );
+    let error = render(&dir, OutputFormat::Markdown).unwrap_err();
+
+    assert_eq!(
+        error.to_string(),
+        "Unknown placeholder `sections.othr` in the template"
+    );
+    assert_eq!(
+        help(&*error).as_deref(),
+        Some("did you mean `sections.other`?")
+    );
+}
+
+#[test]
+fn missing_included_template() {
+    let dir = spec_with_sections("{% include \"chapters/missing.md\" %}", &[]);
+    let error = render(&dir, OutputFormat::Markdown).unwrap_err();
+
+    assert!(
+        error
+            .to_string()
+            .contains("\"chapters/missing.md\" does not exist"),
+        "{error}"
+    );
+}
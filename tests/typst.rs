@@ -0,0 +1,75 @@
+mod common;
+
+use cargo_spec::build::OutputFormat;
+use common::{render_text, spec_with, MANIFEST};
+
+const TEMPLATE: &str = "# Introduction
+
+See [the protocol](#protocol) and [the site](https://example.com).
+
+{{ sections.code }}
+
+# Protocol
+
+- a *list* with `code`
+- costs $5 #1
+";
+
+fn typst() -> String {
+    let manifest = MANIFEST.replace(
+        "authors = []",
+        "version = \"1.0\"\nauthors = [\"Alice \\\"Al\\\"\", \"Bob\"]",
+    );
+    let dir = spec_with(
+        &manifest,
+        TEMPLATE,
+        &[(
+            "lib.rs",
+            "//~ spec:startcode\nfn main() {\n    println!(\"hi\\n\");\n}\n//~ spec:endcode\n",
+        )],
+    );
+    render_text(&dir, OutputFormat::Typst)
+}
+
+#[test]
+fn starts_with_a_title_page() {
+    let typst = typst();
+
+    assert!(typst
+        .starts_with("#set document(title: \"test\", author: (\"Alice \\\"Al\\\"\",\"Bob\",))\n"));
+    assert!(typst.contains(
+        "#page(numbering: none, align(center + horizon)[
+  #text(size: 24pt, weight: \"bold\", \"test\")
+
+  #text(size: 14pt, \"Version 1.0\")
+
+  #text(size: 12pt, \"Alice \\\"Al\\\", Bob\")
+])
+
+#outline()
+#pagebreak()
+"
+    ));
+}
+
+#[test]
+fn converts_the_content() {
+    let typst = typst();
+
+    // headers get a label, which links to headers point to
+    assert!(typst.contains("#heading(level: 1)[Introduction] <introduction>\n"));
+    assert!(typst.contains("#heading(level: 1)[Protocol] <protocol>\n"));
+    assert!(typst.contains(
+        "See #link(label(\"protocol\"))[the protocol]; and #link(\"https://example.com\")[the site];."
+    ));
+
+    // code blocks are listings, with their code as a string literal
+    assert!(typst.contains(
+        "#figure(kind: raw, supplement: [Listing], raw(block: true, lang: \"rust\", \"fn main() {\\n    println!(\\\"hi\\\\n\\\");\\n}\"))\n"
+    ));
+
+    // markup characters of the text are escaped
+    assert!(
+        typst.contains("#list([a #emph[list]; with #raw(\"code\");\n\n], [costs \\$5 \\#1\n\n], )")
+    );
+}